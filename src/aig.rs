@@ -0,0 +1,440 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::mem::size_of;
+use std::ops::Not;
+
+use problem::Quantifier;
+
+/// An edge into the graph: a node index shifted left by one, with the low
+/// bit set when the edge is complemented.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct Lit(u32);
+
+impl Lit {
+    pub const FALSE: Lit = Lit(0);
+    pub const TRUE: Lit = Lit(1);
+
+    pub fn new(node: u32, complemented: bool) -> Lit {
+        Lit((node << 1) | (complemented as u32))
+    }
+
+    pub fn node(self) -> u32 {
+        self.0 >> 1
+    }
+
+    pub fn is_complemented(self) -> bool {
+        self.0 & 1 == 1
+    }
+
+    pub fn positive(self) -> Lit {
+        Lit(self.0 & !1)
+    }
+
+    pub fn negate_if(self, c: bool) -> Lit {
+        Lit(self.0 ^ (c as u32))
+    }
+
+    pub fn is_const(self) -> bool {
+        self.node() == 0
+    }
+}
+
+impl Not for Lit {
+    type Output = Lit;
+
+    fn not(self) -> Lit {
+        Lit(self.0 ^ 1)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Node {
+    False,
+    Var(u32),
+    And(Lit, Lit)
+}
+
+/// An and-inverter graph. Nodes live in a single arena and are only ever
+/// created after their children, so ascending index order is a topological
+/// order. Every `and` goes through one structural hash table.
+#[derive(Debug, Clone)]
+pub struct Aig {
+    nodes: Vec<Node>,
+    // the highest variable each node depends on, kept alongside `nodes`
+    tops: Vec<Option<u32>>,
+    vars: HashMap<u32, Lit>,
+    strash: HashMap<(Lit, Lit), Lit>,
+    // the false and true cofactors of a node with respect to a variable,
+    // kept across calls to `substitute::cofactor_pair`
+    cofactors: HashMap<(u32, u32), (Lit, Lit)>,
+    peak_nodes: usize,
+    simplify_stats: SimplifyStats
+}
+
+/// How often each rule in `Aig::and` fired.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct SimplifyStats {
+    pub hash_hits: u64,
+    pub trivial: u64,
+    pub absorption: u64,
+    pub two_level: u64,
+    pub contradiction: u64,
+    pub new_nodes: u64
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+pub struct MemoryStats {
+    pub nodes: usize,
+    pub peak_nodes: usize,
+    pub hash_entries: usize,
+    pub bytes: usize
+}
+
+#[derive(Debug, Clone)]
+pub struct QBF {
+    pub first_quantifier: Quantifier,
+    pub last_quantifier: Quantifier,
+    pub quantifier_blocks: Vec<u32>,
+    // the name each variable had in the input
+    pub names: Vec<String>,
+    // the first this many variables, all in an outermost existential block,
+    // were used without being quantified in the input
+    pub free_inputs: u32,
+    pub aig: Aig,
+    pub expr: Lit
+}
+
+impl QBF {
+    /// The name `v` had in the input, or its number if it has none.
+    pub fn name(&self, v: u32) -> String {
+        self.names.get(v as usize).cloned().unwrap_or_else(|| v.to_string())
+    }
+
+    pub fn variable(&self, name: &str) -> Option<u32> {
+        self.names.iter().position(|n| n == name).map(|v| v as u32)
+    }
+
+    /// The input names if every one of them is `valid` and they are all
+    /// different, otherwise `x0`, `x1`, ... so the output still reads back.
+    pub fn names_for(&self, valid: &dyn Fn(&str) -> bool) -> Vec<String> {
+        let n_variables: u32 = self.quantifier_blocks.iter().sum();
+        let names: Vec<String> = (0..n_variables).map(|v| self.name(v)).collect();
+        let distinct: HashSet<&String> = names.iter().collect();
+        if distinct.len() == names.len() && names.iter().all(|n| valid(n)) {
+            names
+        } else {
+            (0..n_variables).map(|v| format!("x{}", v)).collect()
+        }
+    }
+}
+
+fn top(tops: &[Option<u32>], node: Node) -> Option<u32> {
+    match node {
+        Node::False => None,
+        Node::Var(v) => Some(v),
+        Node::And(a, b) => tops[a.node() as usize].max(tops[b.node() as usize])
+    }
+}
+
+impl Default for Aig {
+    fn default() -> Aig {
+        Aig::new()
+    }
+}
+
+impl Aig {
+    pub fn new() -> Aig {
+        Aig {
+            nodes: vec![Node::False],
+            tops: vec![None],
+            vars: HashMap::new(),
+            strash: HashMap::new(),
+            cofactors: HashMap::new(),
+            peak_nodes: 1,
+            simplify_stats: SimplifyStats::default()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.len() == 1
+    }
+
+    pub fn node(&self, lit: Lit) -> Node {
+        self.nodes[lit.node() as usize]
+    }
+
+    fn push(&mut self, node: Node) -> Lit {
+        let lit = Lit::new(self.nodes.len() as u32, false);
+        let top = top(&self.tops, node);
+        self.nodes.push(node);
+        self.tops.push(top);
+        lit
+    }
+
+    /// The highest variable `lit` depends on, if any. Cheaper than the full
+    /// support, and enough to tell that a node can't depend on a variable.
+    pub fn top_var(&self, lit: Lit) -> Option<u32> {
+        self.tops[lit.node() as usize]
+    }
+
+    pub fn var(&mut self, v: u32) -> Lit {
+        if let Some(&lit) = self.vars.get(&v) {
+            return lit;
+        }
+        let lit = self.push(Node::Var(v));
+        self.vars.insert(v, lit);
+        lit
+    }
+
+    pub fn true_(&self) -> Lit {
+        Lit::TRUE
+    }
+
+    pub fn false_(&self) -> Lit {
+        Lit::FALSE
+    }
+
+    pub fn not(&self, a: Lit) -> Lit {
+        !a
+    }
+
+    pub fn or(&mut self, a: Lit, b: Lit) -> Lit {
+        let x = self.and(!a, !b);
+        !x
+    }
+
+    /// Conjunction of any number of literals, built as a balanced tree.
+    pub fn and_all(&mut self, lits: &[Lit]) -> Lit {
+        let mut lits = lits.to_vec();
+        if lits.is_empty() {
+            return Lit::TRUE;
+        }
+        while lits.len() > 1 {
+            lits = lits.chunks(2).map(|c| {
+                if c.len() == 2 {
+                    self.and(c[0], c[1])
+                } else {
+                    c[0]
+                }
+            }).collect();
+        }
+        lits[0]
+    }
+
+    pub fn or_all(&mut self, lits: &[Lit]) -> Lit {
+        let negated: Vec<Lit> = lits.iter().map(|&l| !l).collect();
+        !self.and_all(&negated)
+    }
+
+    fn and_children(&self, a: Lit) -> Option<(Lit, Lit)> {
+        match self.node(a) {
+            Node::And(p, q) => Some((p, q)),
+            _ => None
+        }
+    }
+
+    // ~(q & p) & b
+    fn match_and(&mut self, a: Lit, b: Lit) -> Option<Lit> {
+        if !a.is_complemented() {
+            return None;
+        }
+        let (q, p) = self.and_children(a)?;
+        if q == b {
+            Some(self.and(!p, b))
+        } else if p == b {
+            Some(self.and(!q, b))
+        } else if q == !b || p == !b {
+            Some(b)
+        } else {
+            None
+        }
+    }
+
+    /// Every literal that must be true for `a` to be true, found by looking
+    /// through uncomplemented and gates.
+    pub fn implied(&self, a: Lit) -> HashSet<Lit> {
+        let mut implied = HashSet::new();
+        let mut to_visit = vec![a];
+
+        while let Some(x) = to_visit.pop() {
+            if implied.insert(x) && !x.is_complemented() {
+                if let Some((p, q)) = self.and_children(x) {
+                    to_visit.push(p);
+                    to_visit.push(q);
+                }
+            }
+        }
+
+        implied
+    }
+
+    fn simplify_and(&mut self, a: Lit, b: Lit) -> Option<Lit> {
+        if a == Lit::FALSE || b == Lit::FALSE || a == !b {
+            self.simplify_stats.trivial += 1;
+            return Some(Lit::FALSE);
+        }
+        if a == Lit::TRUE || a == b {
+            self.simplify_stats.trivial += 1;
+            return Some(b);
+        }
+        if b == Lit::TRUE {
+            self.simplify_stats.trivial += 1;
+            return Some(a);
+        }
+
+        if !a.is_complemented() {
+            if let Some((p, q)) = self.and_children(a) {
+                if p == b || q == b {
+                    self.simplify_stats.absorption += 1;
+                    return Some(a);
+                }
+            }
+        }
+        if !b.is_complemented() {
+            if let Some((p, q)) = self.and_children(b) {
+                if p == a || q == a {
+                    self.simplify_stats.absorption += 1;
+                    return Some(b);
+                }
+            }
+        }
+
+        if let Some(e) = self.match_and(a, b) {
+            self.simplify_stats.two_level += 1;
+            return Some(e);
+        }
+        if let Some(e) = self.match_and(b, a) {
+            self.simplify_stats.two_level += 1;
+            return Some(e);
+        }
+
+        let a_implied = self.implied(a);
+        let b_implied = self.implied(b);
+        if a_implied.iter().any(|&l| b_implied.contains(&!l)) {
+            self.simplify_stats.contradiction += 1;
+            return Some(Lit::FALSE);
+        }
+
+        None
+    }
+
+    pub fn and(&mut self, a: Lit, b: Lit) -> Lit {
+        let k = if a < b { (a, b) } else { (b, a) };
+        if let Some(&e) = self.strash.get(&k) {
+            self.simplify_stats.hash_hits += 1;
+            return e;
+        }
+
+        let e =
+            match self.simplify_and(k.0, k.1) {
+                Some(e) => e,
+                None => {
+                    self.simplify_stats.new_nodes += 1;
+                    self.push(Node::And(k.0, k.1))
+                }
+            };
+        self.strash.insert(k, e);
+        e
+    }
+
+    /// The nodes reachable from `roots`, in topological order.
+    pub fn cone(&self, roots: &[Lit]) -> Vec<u32> {
+        let mut visited = HashSet::new();
+        let mut to_visit: Vec<u32> = roots.iter().map(|l| l.node()).collect();
+
+        while let Some(n) = to_visit.pop() {
+            if visited.insert(n) {
+                if let Node::And(a, b) = self.nodes[n as usize] {
+                    to_visit.push(a.node());
+                    to_visit.push(b.node());
+                }
+            }
+        }
+
+        let mut cone: Vec<u32> = visited.into_iter().collect();
+        cone.sort_unstable();
+        cone
+    }
+
+    pub fn size(&self, root: Lit) -> usize {
+        self.cone(&[root]).len()
+    }
+
+    /// Compacts the arena down to the nodes reachable from `roots` and
+    /// returns the roots in the new numbering. Any other `Lit` into this
+    /// graph is invalidated.
+    pub fn collect(&mut self, roots: &[Lit]) -> Vec<Lit> {
+        self.peak_nodes = self.peak_nodes.max(self.nodes.len());
+
+        // the renumbering preserves order, so children still come before
+        // parents and normalized hash keys stay normalized
+        let mut map: Vec<Option<u32>> = vec![None; self.nodes.len()];
+        let mut nodes = Vec::new();
+        map[0] = Some(0);
+        nodes.push(Node::False);
+        for n in self.cone(roots) {
+            if n == 0 {
+                continue;
+            }
+            let relit = |l: Lit| Lit::new(map[l.node() as usize].unwrap(), l.is_complemented());
+            let node =
+                match self.nodes[n as usize] {
+                    Node::And(a, b) => Node::And(relit(a), relit(b)),
+                    node => node
+                };
+            map[n as usize] = Some(nodes.len() as u32);
+            nodes.push(node);
+        }
+
+        let relit = |l: Lit| map[l.node() as usize].map(|n| Lit::new(n, l.is_complemented()));
+        self.strash = self.strash.iter().filter_map(|(&(a, b), &e)| {
+            Some(((relit(a)?, relit(b)?), relit(e)?))
+        }).collect();
+        self.vars = self.vars.iter().filter_map(|(&v, &l)| Some((v, relit(l)?))).collect();
+        self.cofactors = self.cofactors.iter().filter_map(|(&(v, n), &(f, t))| {
+            Some(((v, relit(Lit::new(n, false))?.node()), (relit(f)?, relit(t)?)))
+        }).collect();
+        self.tops = Vec::with_capacity(nodes.len());
+        for &node in &nodes {
+            let t = top(&self.tops, node);
+            self.tops.push(t);
+        }
+        self.nodes = nodes;
+        self.nodes.shrink_to_fit();
+
+        roots.iter().map(|&l| relit(l).unwrap()).collect()
+    }
+
+    /// Both cofactors of `node` with respect to `var`, false first, if
+    /// `cofactor_pair` has already built them.
+    pub fn cached_cofactors(&self, var: u32, node: u32) -> Option<(Lit, Lit)> {
+        self.cofactors.get(&(var, node)).cloned()
+    }
+
+    pub fn cache_cofactors(&mut self, var: u32, node: u32, pair: (Lit, Lit)) {
+        self.cofactors.insert((var, node), pair);
+    }
+
+    pub fn simplify_stats(&self) -> SimplifyStats {
+        self.simplify_stats
+    }
+
+    pub fn memory_stats(&self) -> MemoryStats {
+        let entry = size_of::<((Lit, Lit), Lit)>();
+        MemoryStats {
+            nodes: self.nodes.len(),
+            peak_nodes: self.peak_nodes.max(self.nodes.len()),
+            hash_entries: self.strash.len(),
+            bytes:
+                self.nodes.capacity() * size_of::<Node>() +
+                self.tops.capacity() * size_of::<Option<u32>>() +
+                self.strash.capacity() * entry +
+                self.cofactors.capacity() * size_of::<((u32, u32), (Lit, Lit))>() +
+                self.vars.capacity() * size_of::<(u32, Lit)>()
+        }
+    }
+}
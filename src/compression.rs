@@ -0,0 +1,62 @@
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::path::Path;
+
+use bzip2::bufread::MultiBzDecoder;
+use flate2::bufread::MultiGzDecoder;
+use xz2::bufread::XzDecoder;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Xz,
+    Bzip2
+}
+
+impl Compression {
+    pub fn detect(header: &[u8]) -> Compression {
+        if header.starts_with(&[0x1f, 0x8b]) {
+            Compression::Gzip
+        } else if header.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Compression::Xz
+        } else if header.len() >= 4 && header.starts_with(b"BZh") && (b'1'..=b'9').contains(&header[3]) {
+            Compression::Bzip2
+        } else {
+            Compression::None
+        }
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Compression {
+        match path.as_ref().extension().and_then(|e| e.to_str()) {
+            Some("gz") => Compression::Gzip,
+            Some("xz") => Compression::Xz,
+            Some("bz2") => Compression::Bzip2,
+            _ => Compression::None
+        }
+    }
+
+    /// The magic bytes decide, unless there are too few of them to tell, in
+    /// which case the extension of `path` does.
+    pub fn guess(header: &[u8], path: Option<&Path>) -> Compression {
+        match (Compression::detect(header), path) {
+            (Compression::None, Some(path)) if header.len() < 6 => Compression::from_path(path),
+            (c, _) => c
+        }
+    }
+}
+
+/// Wraps `input` in a decoder chosen by its magic bytes. The name of the
+/// file it came from, if any, is only used when the input is too short to
+/// tell.
+pub fn decompress<'a, R: BufRead + 'a>(mut input: R, path: Option<&Path>) -> io::Result<Box<dyn BufRead + 'a>> {
+    let header = input.fill_buf()?;
+    Ok(
+        match Compression::guess(header, path) {
+            Compression::None => Box::new(input),
+            Compression::Gzip => Box::new(BufReader::new(MultiGzDecoder::new(input))),
+            Compression::Xz => Box::new(BufReader::new(XzDecoder::new_multi_decoder(input))),
+            Compression::Bzip2 => Box::new(BufReader::new(MultiBzDecoder::new(input)))
+        })
+}
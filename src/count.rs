@@ -0,0 +1,241 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Instant;
+
+use aig::Aig;
+use aig::Lit;
+use aig::QBF;
+
+use expand_solve::SolverConfig;
+use expand_solve::eliminate_limited;
+
+use preprocess::quantifiers;
+
+use problem::Quantifier;
+use problem::UnknownReason;
+
+use substitute::cofactor_pair;
+
+/// A natural number of any size, as 32-bit digits, least significant first
+/// and without leading zeros.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Natural(Vec<u32>);
+
+impl Natural {
+    pub fn zero() -> Natural {
+        Natural::default()
+    }
+
+    pub fn power_of_two(n: u32) -> Natural {
+        let mut digits = vec![0; (n / 32) as usize];
+        digits.push(1 << (n % 32));
+        Natural(digits)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn trim(mut self) -> Natural {
+        while self.0.last() == Some(&0) {
+            self.0.pop();
+        }
+        self
+    }
+
+    pub fn add(&self, other: &Natural) -> Natural {
+        let mut digits = vec![];
+        let mut carry = 0u64;
+        for i in 0..self.0.len().max(other.0.len()) {
+            let sum = carry + *self.0.get(i).unwrap_or(&0) as u64 + *other.0.get(i).unwrap_or(&0) as u64;
+            digits.push(sum as u32);
+            carry = sum >> 32;
+        }
+        digits.push(carry as u32);
+        Natural(digits).trim()
+    }
+
+    /// `self - other`, which must not be negative.
+    pub fn sub(&self, other: &Natural) -> Natural {
+        assert!(*self >= *other);
+        let mut digits = vec![];
+        let mut borrow = 0i64;
+        for (i, &d) in self.0.iter().enumerate() {
+            let mut diff = d as i64 - borrow - *other.0.get(i).unwrap_or(&0) as i64;
+            borrow = 0;
+            if diff < 0 {
+                diff += 1 << 32;
+                borrow = 1;
+            }
+            digits.push(diff as u32);
+        }
+        Natural(digits).trim()
+    }
+
+    pub fn shl(&self, n: u32) -> Natural {
+        if self.is_zero() {
+            return Natural::zero();
+        }
+        let (words, bits) = ((n / 32) as usize, n % 32);
+        let mut digits = vec![0; words];
+        let mut carry = 0u32;
+        for &d in &self.0 {
+            digits.push(d << bits | carry);
+            carry = if bits == 0 { 0 } else { d >> (32 - bits) };
+        }
+        digits.push(carry);
+        Natural(digits).trim()
+    }
+}
+
+impl From<u64> for Natural {
+    fn from(n: u64) -> Natural {
+        Natural(vec![n as u32, (n >> 32) as u32]).trim()
+    }
+}
+
+impl Ord for Natural {
+    fn cmp(&self, other: &Natural) -> Ordering {
+        self.0.len().cmp(&other.0.len()).then_with(|| self.0.iter().rev().cmp(other.0.iter().rev()))
+    }
+}
+
+impl PartialOrd for Natural {
+    fn partial_cmp(&self, other: &Natural) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Natural {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // nine decimal digits at a time, least significant first
+        let mut chunks = vec![];
+        let mut n = self.0.clone();
+        while !n.is_empty() {
+            let mut remainder = 0u64;
+            for d in n.iter_mut().rev() {
+                let x = remainder << 32 | *d as u64;
+                *d = (x / 1_000_000_000) as u32;
+                remainder = x % 1_000_000_000;
+            }
+            chunks.push(remainder);
+            while n.last() == Some(&0) {
+                n.pop();
+            }
+        }
+        match chunks.pop() {
+            None => write!(f, "0"),
+            Some(top) => {
+                write!(f, "{}", top)?;
+                for chunk in chunks.iter().rev() {
+                    write!(f, "{:09}", chunk)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The number of assignments to variables `0..n_variables` that make `expr`
+/// true, where `expr` mentions no others. Splits on the highest variable
+/// first and remembers the count for every node it meets, so functions that
+/// share subgraphs are only counted once.
+pub fn count_models(aig: &mut Aig, expr: Lit, n_variables: u32) -> Natural {
+    count_models_interruptible(aig, expr, n_variables, &|| None).unwrap()
+}
+
+/// `count_models`, giving up when `interrupted` has a reason to.
+pub fn count_models_interruptible(
+        aig: &mut Aig,
+        expr: Lit,
+        n_variables: u32,
+        interrupted: &dyn Fn() -> Option<UnknownReason>) -> Result<Natural, UnknownReason>
+{
+    // for every node, its count over the variables up to and including its
+    // top one, and while that is pending, its two cofactors
+    let mut counts: HashMap<u32, Natural> = HashMap::new();
+    let mut cofactors: HashMap<u32, (Lit, Lit)> = HashMap::new();
+
+    let mut to_visit = vec![];
+    if !expr.is_const() {
+        to_visit.push(expr.node());
+    }
+    while let Some(&n) = to_visit.last() {
+        if counts.contains_key(&n) {
+            to_visit.pop();
+            continue;
+        }
+        let lit = Lit::new(n, false);
+        let top = aig.top_var(lit).unwrap();
+        let (f, t) =
+            match cofactors.get(&n) {
+                Some(&pair) => pair,
+                None => {
+                    let pair = cofactor_pair(aig, lit, top, interrupted)?;
+                    cofactors.insert(n, pair);
+                    pair
+                }
+            };
+        let pending: Vec<u32> = [f, t].iter()
+            .filter(|l| !l.is_const() && !counts.contains_key(&l.node()))
+            .map(|l| l.node())
+            .collect();
+        if !pending.is_empty() {
+            to_visit.extend(pending);
+            continue;
+        }
+        let count = value(aig, &counts, f, top).add(&value(aig, &counts, t, top));
+        counts.insert(n, count);
+        cofactors.remove(&n);
+        to_visit.pop();
+    }
+
+    Ok(value(aig, &counts, expr, n_variables))
+}
+
+// the count of `lit` over `0..n_variables`, from that of its node
+fn value(aig: &Aig, counts: &HashMap<u32, Natural>, lit: Lit, n_variables: u32) -> Natural {
+    match lit {
+        Lit::TRUE => Natural::power_of_two(n_variables),
+        Lit::FALSE => Natural::zero(),
+        _ => {
+            let top = aig.top_var(lit).unwrap();
+            let count = &counts[&lit.node()];
+            let count =
+                if lit.is_complemented() {
+                    Natural::power_of_two(top + 1).sub(count)
+                } else {
+                    count.clone()
+                };
+            count.shl(n_variables - top - 1)
+        }
+    }
+}
+
+/// The number of assignments to the outermost block under which the rest
+/// of the problem is true, once every inner block has been expanded. When
+/// the outermost block is universal there is nothing to count over, and the
+/// answer is 1 or 0.
+pub fn count(qbf: QBF) -> Natural {
+    count_with_config(qbf, &SolverConfig::default()).unwrap()
+}
+
+/// `count` within the limits of `config`.
+pub fn count_with_config(qbf: QBF, config: &SolverConfig) -> Result<Natural, UnknownReason> {
+    let start = Instant::now();
+    let prefix = quantifiers(&qbf);
+    let outer =
+        match qbf.first_quantifier {
+            Quantifier::Exists => qbf.quantifier_blocks.first().cloned().unwrap_or(0),
+            Quantifier::ForAll => 0
+        };
+    let inner: Vec<(Quantifier, u32)> = prefix.iter().enumerate()
+        .skip(outer as usize)
+        .map(|(v, &q)| (q, v as u32))
+        .collect();
+    let mut aig = qbf.aig;
+    let expr = eliminate_limited(&mut aig, qbf.expr, &inner, config, start)?;
+    log!(1, "expanded the inner blocks to {} nodes", aig.size(expr));
+    count_models_interruptible(&mut aig, expr, outer, &|| config.interrupted(start))
+}
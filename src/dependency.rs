@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::mem;
+
+use aig::Aig;
+use aig::Lit;
+use aig::Node;
+use aig::QBF;
+
+use introduce::quantifier_blocks;
+
+use preprocess::quantifiers;
+
+use problem::Quantifier;
+
+use substitute::rename;
+
+use support::Supports;
+use support::VarSet;
+
+// the leaves of the and tree at the top of `root`, which are conjuncts of the
+// matrix, or the negated disjuncts when `root` is complemented
+fn parts(aig: &Aig, root: Lit) -> Vec<Lit> {
+    let mut parts = vec![];
+    let mut visited = HashSet::new();
+    let mut to_visit = vec![root.positive()];
+    while let Some(l) = to_visit.pop() {
+        if !visited.insert(l) {
+            continue;
+        }
+        match aig.node(l) {
+            Node::And(a, b) if !l.is_complemented() => {
+                to_visit.push(a);
+                to_visit.push(b);
+            },
+            _ => parts.push(l)
+        }
+    }
+    parts
+}
+
+fn find(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+/// The standard dependency scheme, with the conjuncts of the matrix taking
+/// the place of clauses: `y` depends on an `x` in an earlier block with the
+/// other quantifier when a chain of conjuncts, each sharing an existential
+/// from `x`'s block or later with the next, leads from `x` to `y`. For a
+/// negated matrix, the disjuncts are chained by universals instead. Returns,
+/// for every variable, the variables that depend on it.
+pub fn dependents(qbf: &QBF) -> Vec<VarSet> {
+    let quantifiers = quantifiers(qbf);
+    let n = quantifiers.len();
+    let mut block = vec![];
+    for (b, &size) in qbf.quantifier_blocks.iter().enumerate() {
+        block.extend((0..size).map(|_| b));
+    }
+    let connector = if qbf.expr.is_complemented() { Quantifier::ForAll } else { Quantifier::Exists };
+
+    let mut supports = Supports::new();
+    let parts = parts(&qbf.aig, qbf.expr);
+    let mut component: Vec<VarSet> = parts.iter().map(|&p| supports.support(&qbf.aig, p).clone()).collect();
+    let mut containing = vec![vec![]; n];
+    for (i, support) in component.iter().enumerate() {
+        for v in support.iter().filter(|&v| (v as usize) < n) {
+            containing[v as usize].push(i);
+        }
+    }
+
+    // innermost block first, joining the parts that share a connector of
+    // this block or a later one, each root keeping its component's support
+    let mut parent: Vec<usize> = (0..parts.len()).collect();
+    let mut dependents = vec![VarSet::new(); n];
+    let mut end = n;
+    for (b, &size) in qbf.quantifier_blocks.iter().enumerate().rev() {
+        let start = end - size as usize;
+        for v in start..end {
+            if quantifiers[v] != connector {
+                continue;
+            }
+            for w in containing[v].windows(2) {
+                let (r0, r1) = (find(&mut parent, w[0]), find(&mut parent, w[1]));
+                if r0 != r1 {
+                    parent[r1] = r0;
+                    let support = mem::take(&mut component[r1]);
+                    component[r0].union_with(&support);
+                }
+            }
+        }
+        for x in start..end {
+            let mut reached = VarSet::new();
+            for &p in &containing[x] {
+                let r = find(&mut parent, p);
+                reached.union_with(&component[r]);
+            }
+            for y in reached.iter() {
+                let y = y as usize;
+                if y < n && block[y] > b && quantifiers[y] != quantifiers[x] {
+                    dependents[x].insert(y as u32);
+                }
+            }
+        }
+        end = start;
+    }
+    dependents
+}
+
+/// Moves every variable out to the first block of its quantifier that
+/// follows everything it depends on, which can merge blocks and leaves the
+/// inner blocks, expanded first, with only the variables that have to be
+/// there. Variables never move inwards, so blocks only ever get fewer.
+pub fn relax_prefix(mut qbf: QBF) -> QBF {
+    let quantifiers = quantifiers(&qbf);
+    let dependents = dependents(&qbf);
+    let first = qbf.first_quantifier;
+    let before = qbf.quantifier_blocks.len();
+
+    // levels alternate starting from `first`; everything that a variable
+    // depends on comes before it, so has its level already
+    let mut level = vec![0; quantifiers.len()];
+    for (x, deps) in dependents.iter().enumerate() {
+        let earliest = if quantifiers[x] == first { 0 } else { 1 };
+        level[x] = level[x].max(earliest);
+        for y in deps.iter() {
+            let y = y as usize;
+            let mut l = level[x] + 1;
+            if (l % 2 == 0) != (quantifiers[y] == first) {
+                l += 1;
+            }
+            level[y] = level[y].max(l);
+        }
+    }
+
+    let mut order: Vec<u32> = (0..quantifiers.len() as u32).collect();
+    order.sort_by_key(|&v| level[v as usize]);
+    let numbering: HashMap<u32, u32> = order.iter().enumerate().map(|(i, &v)| (v, i as u32)).collect();
+    let expr = rename(&mut qbf.aig, qbf.expr, &|v| numbering[&v]);
+    qbf.expr = qbf.aig.collect(&[expr])[0];
+    qbf.names = order.iter().map(|&v| qbf.name(v)).collect();
+    let prefix: Vec<Quantifier> = order.iter().map(|&v| quantifiers[v as usize]).collect();
+    let (first_quantifier, last_quantifier, blocks) = quantifier_blocks(&prefix);
+    qbf.first_quantifier = first_quantifier;
+    qbf.last_quantifier = last_quantifier;
+    qbf.quantifier_blocks = blocks;
+    log!(1, "relaxed the prefix from {} to {} blocks", before, qbf.quantifier_blocks.len());
+    qbf
+}
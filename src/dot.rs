@@ -1,77 +1,237 @@
 use std::collections::HashMap;
-use std::rc::Rc;
 
-use rc_expression::Exp;
+use aig::Aig;
+use aig::Lit;
+use aig::Node;
+use aig::QBF;
 
-struct ExpPrinter {
+use problem::Quantifier;
+use problem::opposite_quantifier;
+
+use support::Supports;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Negation {
+    // a "not" node, shared between every use of the same complemented literal
+    Node,
+    Dashed,
+    // an edge ending in a hollow dot
+    Dot
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Coloring {
+    None,
+    // by the innermost quantifier block a node depends on
+    Level,
+    // darker the more variables a node depends on
+    Support
+}
+
+#[derive(Debug, Clone)]
+pub struct DotOptions {
+    pub negation: Negation,
+    pub coloring: Coloring,
+    // draw only the cone of this literal instead of the whole output
+    pub root: Option<Lit>,
+    // and only this many levels of it; the nodes where it is cut off are
+    // drawn as boxes
+    pub depth: Option<usize>
+}
+
+impl Default for DotOptions {
+    fn default() -> DotOptions {
+        DotOptions {
+            negation: Negation::Node,
+            coloring: Coloring::None,
+            root: None,
+            depth: None
+        }
+    }
+}
+
+const PALETTE: [&str; 6] = ["lightblue", "lightpink", "palegreen", "khaki", "plum", "lightsalmon"];
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// the quantifier and block of every variable
+fn levels(qbf: &QBF) -> Vec<(Quantifier, usize)> {
+    let mut levels = vec![];
+    let mut quantifier = qbf.first_quantifier;
+    for (level, &block) in qbf.quantifier_blocks.iter().enumerate() {
+        for _ in 0..block {
+            levels.push((quantifier, level));
+        }
+        quantifier = opposite_quantifier(quantifier);
+    }
+    levels
+}
+
+struct Printer<'a> {
+    aig: &'a Aig,
+    qbf: Option<&'a QBF>,
+    options: &'a DotOptions,
     output: String,
-    next_var: u32,
-    serialized: HashMap<*const (), String>
+    negations: HashMap<Lit, String>
 }
 
-impl ExpPrinter {
-    fn new() -> ExpPrinter {
-        ExpPrinter {
-            output: "digraph g {\n".to_string(),
-            serialized: HashMap::new(),
-            next_var: 1
+impl<'a> Printer<'a> {
+    // every node by its index, variables included, as `--root` takes them
+    fn id(&self, n: u32) -> String {
+        format!("n{}", n)
+    }
+
+    // the end of an edge to `lit`, and the edge's attributes
+    fn target(&mut self, lit: Lit) -> (String, Vec<String>) {
+        let id = self.id(lit.node());
+        if !lit.is_complemented() {
+            return (id, vec![]);
+        }
+        match self.options.negation {
+            Negation::Node => {
+                if let Some(v) = self.negations.get(&lit) {
+                    return (v.clone(), vec![]);
+                }
+                let v = format!("not{}", lit.node());
+                self.output.push_str(&format!("  {} [label=\"not\"];\n", &v));
+                self.output.push_str(&format!("  {} -> {};\n", &v, id));
+                self.negations.insert(lit, v.clone());
+                (v, vec![])
+            },
+            Negation::Dashed => (id, vec!["style=dashed".to_string()]),
+            Negation::Dot => (id, vec!["arrowhead=odot".to_string()])
         }
     }
 
-    fn new_var(&mut self) -> String {
-        let n = self.next_var;
-        self.next_var += 1;
-        format!("v{}", n)
+    fn edge(&mut self, from: &str, to: Lit, label: Option<&str>) {
+        let (to, mut attributes) = self.target(to);
+        if let Some(label) = label {
+            attributes.push(format!("label=\"{}\"", label));
+        }
+        if attributes.is_empty() {
+            self.output.push_str(&format!("  {} -> {};\n", from, to));
+        } else {
+            self.output.push_str(&format!("  {} -> {} [{}];\n", from, to, attributes.join(", ")));
+        }
     }
 
-    fn build(&mut self, exp: Rc<Exp>) -> String {
-        let expr_ptr = &*exp as *const _ as *const ();
-        match self.serialized.get(&expr_ptr).map(|v| v.clone()) {
-            Some(v) => v.clone(),
-            None => {
-                let outcome =
-                    match *exp {
-                        Exp::And(ref a, ref b) => {
-                            let a1 = self.build(a.clone());
-                            let b1 = self.build(b.clone());
-                            let v = self.new_var();
-                            self.output.push_str(&format!("  {} [label=\"and\"];\n", &v));
-                            self.output.push_str(&format!("  {} -> {} [label=\"l\"];\n", &v, a1));
-                            self.output.push_str(&format!("  {} -> {} [label=\"r\"];\n", &v, b1));
-                            v
-                        },
-                        Exp::Not(ref a) => {
-                            let a1 = self.build(a.clone());
-                            let v = self.new_var();
-                            self.output.push_str(&format!("  {} [label=\"not\"];\n", &v));
-                            self.output.push_str(&format!("  {} -> {};\n", &v, a1));
-                            v
-                        },
-                        Exp::Var(n) =>
-                            format!("arg_{}", n),
-                        Exp::True => {
-                            let v = self.new_var();
-                            self.output.push_str(&format!("  {} [label=\"true\"];\n", &v));
-                            v
-                        },
-                        Exp::False => {
-                            let v = self.new_var();
-                            self.output.push_str(&format!("  {} [label=\"false\"];\n", &v));
-                            v
-                        },
-                    };
-                self.serialized.insert(expr_ptr, outcome.clone());
-                outcome
+    fn build(&mut self, root: Lit) {
+        // everything within `depth` of the root, found parents first
+        let cone = self.aig.cone(&[root]);
+        let mut depth: HashMap<u32, usize> = HashMap::new();
+        depth.insert(root.node(), 0);
+        for &n in cone.iter().rev() {
+            let d =
+                match depth.get(&n) {
+                    Some(&d) => d,
+                    None => continue
+                };
+            if self.options.depth.is_some_and(|max| d >= max) {
+                continue;
+            }
+            if let Node::And(a, b) = self.aig.node(Lit::new(n, false)) {
+                for c in &[a.node(), b.node()] {
+                    let e = depth.entry(*c).or_insert(d + 1);
+                    *e = (*e).min(d + 1);
+                }
+            }
+        }
+
+        let levels = self.qbf.map(levels).unwrap_or_default();
+        let n_variables = levels.len().max(1);
+        // innermost level, in the same order as `cone`
+        let mut level: HashMap<u32, usize> = HashMap::new();
+        let mut supports = Supports::new();
+
+        for &n in &cone {
+            let node = self.aig.node(Lit::new(n, false));
+            match node {
+                Node::Var(v) => {
+                    level.insert(n, levels.get(v as usize).map_or(0, |&(_, l)| l));
+                },
+                Node::And(a, b) => {
+                    let l = level[&a.node()].max(level[&b.node()]);
+                    level.insert(n, l);
+                },
+                Node::False => {
+                    level.insert(n, 0);
+                }
+            }
+            if !depth.contains_key(&n) {
+                continue;
+            }
+
+            let id = self.id(n);
+            let mut attributes =
+                match node {
+                    Node::Var(v) => {
+                        let name = self.qbf.map_or_else(|| format!("arg_{}", v), |qbf| qbf.name(v));
+                        match levels.get(v as usize) {
+                            Some(&(Quantifier::Exists, _)) => vec![format!("label={}", quote(&format!("∃ {}", name)))],
+                            Some(&(Quantifier::ForAll, _)) => vec![format!("label={}", quote(&format!("∀ {}", name)))],
+                            None => vec![format!("label={}", quote(&name))]
+                        }
+                    },
+                    Node::And(..) if self.options.depth == Some(depth[&n]) =>
+                        vec!["label=\"and …\"".to_string(), "shape=box".to_string()],
+                    Node::And(..) => vec!["label=\"and\"".to_string()],
+                    Node::False => vec!["label=\"false\"".to_string()]
+                };
+            let color =
+                match self.options.coloring {
+                    Coloring::None => None,
+                    Coloring::Level => Some(PALETTE[level[&n] % PALETTE.len()].to_string()),
+                    Coloring::Support => {
+                        // the matrix may mention variables that the prefix doesn't
+                        let support = supports.support(self.aig, Lit::new(n, false)).len().min(n_variables);
+                        Some(format!("gray{}", 100 - 60 * support / n_variables))
+                    }
+                };
+            if let Some(color) = color {
+                attributes.push("style=filled".to_string());
+                attributes.push(format!("fillcolor={}", color));
+            }
+            if !attributes.is_empty() {
+                self.output.push_str(&format!("  {} [{}];\n", id, attributes.join(", ")));
+            }
+
+            if let Node::And(a, b) = node {
+                if self.options.depth != Some(depth[&n]) {
+                    self.edge(&id, a, Some("l"));
+                    self.edge(&id, b, Some("r"));
+                }
             }
         }
+        self.output.push_str("  out [label=\"output\", shape=plaintext];\n");
+        self.edge("out", root, None);
+        self.output.push('}');
     }
 }
 
-pub fn printout(exp: Rc<Exp>) -> String {
-    let mut printer = ExpPrinter::new();
-    let out = printer.build(exp);
-    printer.output.push_str("  out [label=\"output\"];\n");
-    printer.output.push_str(&format!("  out -> {}\n", &out));
-    printer.output.push_str("}");
+fn render(aig: &Aig, qbf: Option<&QBF>, exp: Lit, options: &DotOptions) -> String {
+    let mut printer = Printer {
+        aig,
+        qbf,
+        options,
+        output: "digraph aig {\n".to_string(),
+        negations: HashMap::new()
+    };
+    printer.build(options.root.unwrap_or(exp));
     printer.output
 }
+
+pub fn printout(aig: &Aig, exp: Lit) -> String {
+    render(aig, None, exp, &DotOptions::default())
+}
+
+/// Like `printout`, but labels variables with their input names and
+/// quantifiers.
+pub fn printout_qbf(qbf: &QBF) -> String {
+    printout_with(qbf, &DotOptions::default())
+}
+
+pub fn printout_with(qbf: &QBF, options: &DotOptions) -> String {
+    render(&qbf.aig, Some(qbf), qbf.expr, options)
+}
@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use aig::Aig;
+use aig::Lit;
+use aig::QBF;
+
+use expand_solve::GC_FACTOR;
+use expand_solve::SolverConfig;
+
+use introduce::quantifier_blocks;
+
+use preprocess::quantifiers;
+
+use problem::Quantifier;
+use problem::Solution;
+use problem::UnknownReason;
+
+use substitute::cofactor_pair;
+use substitute::rename;
+
+use support::Supports;
+use support::VarSet;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Binding {
+    Universal,
+    // the universals that its value may depend on
+    Existential(VarSet)
+}
+
+/// A problem with a Henkin prefix: rather than depending on the universals
+/// before it, every existential lists the universals it may depend on.
+#[derive(Debug, Clone)]
+pub struct DQBF {
+    pub bindings: Vec<Binding>,
+    pub names: Vec<String>,
+    pub aig: Aig,
+    pub expr: Lit
+}
+
+impl DQBF {
+    /// Every existential depends on the universals in front of it.
+    pub fn from_qbf(qbf: QBF) -> DQBF {
+        let mut universals = VarSet::new();
+        let mut bindings = vec![];
+        for (v, q) in quantifiers(&qbf).into_iter().enumerate() {
+            match q {
+                Quantifier::ForAll => {
+                    universals.insert(v as u32);
+                    bindings.push(Binding::Universal);
+                },
+                Quantifier::Exists => bindings.push(Binding::Existential(universals.clone()))
+            }
+        }
+        let names = (0..bindings.len() as u32).map(|v| qbf.name(v)).collect();
+        DQBF { bindings, names, aig: qbf.aig, expr: qbf.expr }
+    }
+
+    pub fn name(&self, v: u32) -> String {
+        self.names.get(v as usize).cloned().unwrap_or_else(|| v.to_string())
+    }
+
+    /// The same problem with a linear prefix, which exists when the
+    /// dependency sets are nested in one another. Otherwise gives the problem
+    /// back unchanged.
+    pub fn into_qbf(mut self) -> Result<QBF, Box<DQBF>> {
+        let mut universals = VarSet::new();
+        let mut levels: Vec<&VarSet> = vec![];
+        for (v, b) in self.bindings.iter().enumerate() {
+            match *b {
+                Binding::Universal => universals.insert(v as u32),
+                Binding::Existential(ref deps) => levels.push(deps)
+            }
+        }
+        levels.push(&universals);
+        levels.sort_by_key(|deps| deps.len());
+        levels.dedup();
+        if levels.windows(2).any(|w| !w[0].is_subset(w[1])) {
+            return Err(Box::new(self));
+        }
+
+        // each level's new universals, then the existentials that see them
+        let mut order = vec![];
+        let mut prefix = vec![];
+        let mut placed = VarSet::new();
+        for level in levels {
+            for u in level.iter() {
+                if !placed.contains(u) {
+                    order.push(u);
+                    prefix.push(Quantifier::ForAll);
+                }
+            }
+            placed.union_with(level);
+            for (v, b) in self.bindings.iter().enumerate() {
+                if let Binding::Existential(ref deps) = *b {
+                    if deps == level {
+                        order.push(v as u32);
+                        prefix.push(Quantifier::Exists);
+                    }
+                }
+            }
+        }
+
+        let numbering: HashMap<u32, u32> = order.iter().enumerate().map(|(i, &v)| (v, i as u32)).collect();
+        let expr = rename(&mut self.aig, self.expr, &|v| numbering[&v]);
+        let expr = self.aig.collect(&[expr])[0];
+        let names = order.iter().map(|&v| self.name(v)).collect();
+        let (first_quantifier, last_quantifier, blocks) = quantifier_blocks(&prefix);
+        Ok(QBF {
+            first_quantifier,
+            last_quantifier,
+            quantifier_blocks: blocks,
+            names,
+            free_inputs: 0,
+            aig: self.aig,
+            expr
+        })
+    }
+}
+
+pub fn solve(problem: DQBF) -> Solution {
+    solve_with_config(problem, &SolverConfig::default())
+}
+
+/// Expands universals, giving every existential that depends on one a copy
+/// for each of its values, until some existential may depend on all of the
+/// universals left; that one is expanded as the innermost variable of a QBF
+/// would be. Linear prefixes are the special case where no copies are
+/// needed. Of the `config`, only the limits are used.
+pub fn solve_with_config(problem: DQBF, config: &SolverConfig) -> Solution {
+    let start = Instant::now();
+    let interrupted = || config.interrupted(start);
+    let DQBF { mut bindings, mut names, mut aig, mut expr } = problem;
+
+    while !expr.is_const() {
+        if let Some(reason) = config.interrupted(start) {
+            return Solution::Unknown(reason);
+        }
+        // universals that don't occur don't need to be seen
+        let support = Supports::new().support(&aig, expr).clone();
+        let mut universals = VarSet::new();
+        for v in support.iter() {
+            if bindings[v as usize] == Binding::Universal {
+                universals.insert(v);
+            }
+        }
+        let innermost = support.iter().find(|&v| {
+            match bindings[v as usize] {
+                Binding::Existential(ref deps) => universals.is_subset(deps),
+                Binding::Universal => false
+            }
+        });
+
+        let expanded =
+            match innermost {
+                Some(v) => cofactor_pair(&mut aig, expr, v, &interrupted).map(|(f, t)| {
+                    log!(1, "expanded {}", names[v as usize]);
+                    aig.or(f, t)
+                }),
+                None => {
+                    // the universal that forces the fewest copies
+                    let dependents = |u: u32| support.iter().filter(|&v| {
+                        matches!(bindings[v as usize], Binding::Existential(ref deps) if deps.contains(u))
+                    }).collect::<Vec<u32>>();
+                    let u = universals.iter().min_by_key(|&u| dependents(u).len()).unwrap();
+                    let copied = dependents(u);
+                    cofactor_pair(&mut aig, expr, u, &interrupted).map(|(f, t)| {
+                        // the true cofactor gets the copies
+                        let mut copies = HashMap::new();
+                        for &v in &copied {
+                            if let Binding::Existential(ref mut deps) = bindings[v as usize] {
+                                deps.remove(u);
+                            }
+                            copies.insert(v, bindings.len() as u32);
+                            let copy = bindings[v as usize].clone();
+                            bindings.push(copy);
+                            names.push(format!("{}[{}]", names[v as usize], names[u as usize]));
+                        }
+                        let t = rename(&mut aig, t, &|v| copies.get(&v).cloned().unwrap_or(v));
+                        log!(1, "expanded {} copying {} existentials", names[u as usize], copied.len());
+                        aig.and(f, t)
+                    })
+                }
+            };
+        expr =
+            match expanded {
+                Ok(e) => e,
+                Err(reason) => return Solution::Unknown(reason)
+            };
+
+        let size = aig.size(expr);
+        if config.max_size.is_some_and(|max| size > max) {
+            return Solution::Unknown(UnknownReason::SizeLimit);
+        }
+        if aig.len() > GC_FACTOR * size.max(config.gc_min_nodes) {
+            expr = aig.collect(&[expr])[0];
+        }
+    }
+
+    if expr == Lit::TRUE {
+        Solution::Sat
+    } else {
+        Solution::Unsat
+    }
+}
@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+use std::io::BufRead;
+
+use dqbf::Binding;
+use dqbf::DQBF;
+
+use qdimacs::Prefix;
+use qdimacs::read_with;
+
+use support::VarSet;
+
+// a prefix where every existential has its own dependency set
+#[derive(Default)]
+struct Dependencies {
+    numbering: HashMap<i64, u32>,
+    bindings: Vec<Binding>,
+    names: Vec<String>,
+    universals: VarSet
+}
+
+impl Prefix for Dependencies {
+    fn declare(&mut self, tag: char, vars: &[i64]) -> Result<(), String> {
+        // a `d` line binds only its first variable
+        let (bound, dependencies) =
+            if tag == 'd' {
+                if vars.is_empty() {
+                    return Err("dependency line without a variable".to_string());
+                }
+                vars.split_at(1)
+            } else {
+                (vars, &[][..])
+            };
+        let mut deps = VarSet::new();
+        for u in dependencies {
+            match self.numbering.get(u) {
+                Some(&u) if self.bindings[u as usize] == Binding::Universal => deps.insert(u),
+                _ => return Err(format!("{} is not a universal", u))
+            }
+        }
+        for &v in bound {
+            if v <= 0 || self.numbering.contains_key(&v) {
+                return Err(format!("bad variable {}", v));
+            }
+            let var = self.bindings.len() as u32;
+            self.numbering.insert(v, var);
+            self.names.push(v.to_string());
+            let binding =
+                match tag {
+                    'a' => {
+                        self.universals.insert(var);
+                        Binding::Universal
+                    },
+                    'e' => Binding::Existential(self.universals.clone()),
+                    _ => Binding::Existential(deps.clone())
+                };
+            self.bindings.push(binding);
+        }
+        Ok(())
+    }
+
+    fn variable(&mut self, number: i64) -> u32 {
+        let bindings = &mut self.bindings;
+        let names = &mut self.names;
+        *self.numbering.entry(number).or_insert_with(|| {
+            bindings.push(Binding::Existential(VarSet::new()));
+            names.push(number.to_string());
+            bindings.len() as u32 - 1
+        })
+    }
+}
+
+/// Reads DQDIMACS: QDIMACS with `d` lines, each giving an existential and
+/// then the universals it depends on. An existential on an `e` line depends
+/// on every universal declared before it, and a variable that is not
+/// declared at all depends on none.
+pub fn read<R: BufRead>(input: R) -> Result<DQBF, String> {
+    let mut prefix = Dependencies::default();
+    let (aig, expr) = read_with(input, "aed", &mut prefix)?;
+    Ok(DQBF { bindings: prefix.bindings, names: prefix.names, aig, expr })
+}
+
+pub fn parse(input: &[u8]) -> Result<DQBF, String> {
+    read(input)
+}
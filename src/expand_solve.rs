@@ -1,47 +1,413 @@
-use std::rc::Rc;
+use std::io;
+use std::mem;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use std::time::Instant;
 
 use problem::Quantifier;
 use problem::Solution;
+use problem::UnknownReason;
 use problem::opposite_quantifier;
 
-use rc_expression::Exp as RExp;
-use rc_expression::QBF;
-use rc_expression::Builder;
+use aig::Aig;
+use aig::Lit;
+use aig::Node;
+use aig::QBF;
 
-use rc_substitute::substitute;
+use introduce::quantifier_blocks;
 
-fn expand(quantifier: Quantifier, var: u32, exp: Rc<RExp>) -> Rc<RExp> {
-    let mut builder = Builder::new();
-    let false_expr = substitute(&mut builder, exp.clone(), var, false);
-    let true_expr = substitute(&mut builder, exp, var, true);
-    let output =
+use preprocess::quantifiers;
+
+use stats::ExpansionStep;
+use stats::SolveStats;
+
+use substitute::cofactor_pair;
+use substitute::cofactors;
+use substitute::substitute;
+
+use trace::TraceConfig;
+use trace::Tracer;
+
+// collect garbage once the arena holds this many times more nodes than the
+// current expression needs, and at least `SolverConfig::gc_min_nodes`
+pub const GC_FACTOR: usize = 4;
+pub const GC_MIN_NODES: usize = 1 << 16;
+
+// an assignment to the outermost block, one value per variable
+pub type Witness = Vec<(u32, bool)>;
+
+// a block of k variables has 2^k cofactors, so don't go much further
+const MAX_BLOCK_SIZE: usize = 8;
+
+#[derive(Debug, Clone)]
+pub struct SolverConfig {
+    pub timeout: Option<Duration>,
+    // checked against `Aig::size` of the expression after every expansion
+    pub max_size: Option<usize>,
+    pub cancel: Option<Arc<AtomicBool>>,
+    // expand up to this many variables of a block at once, from one
+    // traversal; 0 and 1 both mean one at a time
+    pub block_size: usize,
+    // don't measure the expression after every step unless `max_size`
+    // needs it; steps then have no size
+    pub skip_size: bool,
+    // the arena is never collected below this many nodes
+    pub gc_min_nodes: usize
+}
+
+impl Default for SolverConfig {
+    fn default() -> SolverConfig {
+        SolverConfig {
+            timeout: None,
+            max_size: None,
+            cancel: None,
+            block_size: 0,
+            skip_size: false,
+            gc_min_nodes: GC_MIN_NODES
+        }
+    }
+}
+
+impl SolverConfig {
+    pub fn interrupted(&self, start: Instant) -> Option<UnknownReason> {
+        if let Some(ref cancel) = self.cancel {
+            if cancel.load(Ordering::Relaxed) {
+                return Some(UnknownReason::Cancelled);
+            }
+        }
+        if let Some(timeout) = self.timeout {
+            if start.elapsed() >= timeout {
+                return Some(UnknownReason::Timeout);
+            }
+        }
+        None
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Control {
+    Continue,
+    Abort
+}
+
+/// Told about every expansion as it happens. `elapsed` is measured from the
+/// start of the solve. When a whole block is expanded at once, `var` is its
+/// outermost variable.
+pub trait Observer {
+    fn expanded(&mut self, var: u32, quantifier: Quantifier, size: Option<usize>, elapsed: Duration) -> Control;
+}
+
+impl<F: FnMut(u32, Quantifier, Option<usize>, Duration) -> Control> Observer for F {
+    fn expanded(&mut self, var: u32, quantifier: Quantifier, size: Option<usize>, elapsed: Duration) -> Control {
+        self(var, quantifier, size, elapsed)
+    }
+}
+
+fn expand(
+        aig: &mut Aig,
+        quantifier: Quantifier,
+        var: u32,
+        exp: Lit,
+        interrupted: &dyn Fn() -> Option<UnknownReason>) -> Result<Lit, UnknownReason>
+{
+    let (false_expr, true_expr) = cofactor_pair(aig, exp, var, interrupted)?;
+    Ok(
         match quantifier {
-            Quantifier::ForAll => builder.and(false_expr, true_expr),
-            Quantifier::Exists => builder.or(false_expr, true_expr)
-        };
-    output
+            Quantifier::ForAll => aig.and(false_expr, true_expr),
+            Quantifier::Exists => aig.or(false_expr, true_expr)
+        })
+}
+
+fn expand_block(
+        aig: &mut Aig,
+        quantifier: Quantifier,
+        vars: &[u32],
+        exp: Lit,
+        interrupted: &dyn Fn() -> Option<UnknownReason>) -> Result<Lit, UnknownReason>
+{
+    if let [var] = *vars {
+        return expand(aig, quantifier, var, exp, interrupted);
+    }
+    let cofactors = cofactors(aig, exp, vars, interrupted)?;
+    Ok(
+        match quantifier {
+            Quantifier::ForAll => aig.and_all(&cofactors),
+            Quantifier::Exists => aig.or_all(&cofactors)
+        })
+}
+
+/// Quantifies out the variables of `prefix`, innermost (last) first, and
+/// returns what is left: a function of the variables that `prefix` does not
+/// mention, equivalent to `expr` under that prefix.
+pub fn eliminate(aig: &mut Aig, expr: Lit, prefix: &[(Quantifier, u32)]) -> Lit {
+    let mut expr = expr;
+    for &(quantifier, var) in prefix.iter().rev() {
+        expr = expand(aig, quantifier, var, expr, &|| None).unwrap();
+    }
+    expr
+}
+
+/// `eliminate` within the limits of `config`, timed from `start`. Gives up
+/// with the reason when one is reached.
+pub fn eliminate_limited(
+        aig: &mut Aig,
+        expr: Lit,
+        prefix: &[(Quantifier, u32)],
+        config: &SolverConfig,
+        start: Instant) -> Result<Lit, UnknownReason>
+{
+    let interrupted = || config.interrupted(start);
+    let mut expr = expr;
+    for &(quantifier, var) in prefix.iter().rev() {
+        if let Some(reason) = interrupted() {
+            return Err(reason);
+        }
+        expr = expand(aig, quantifier, var, expr, &interrupted)?;
+        if config.max_size.is_some_and(|max| aig.size(expr) > max) {
+            return Err(UnknownReason::SizeLimit);
+        }
+    }
+    Ok(expr)
+}
+
+/// `eliminate` for a whole problem: quantifies out every variable but its
+/// free inputs, and returns a function of variables `0..free_inputs`.
+pub fn eliminate_bound(qbf: &mut QBF) -> Lit {
+    let prefix: Vec<(Quantifier, u32)> = quantifiers(qbf).into_iter().enumerate()
+        .skip(qbf.free_inputs as usize)
+        .map(|(v, q)| (q, v as u32))
+        .collect();
+    eliminate(&mut qbf.aig, qbf.expr, &prefix)
+}
+
+pub fn solve(problem: QBF) -> Solution {
+    solve_with_config(problem, &SolverConfig::default())
+}
+
+pub fn solve_with_config(problem: QBF, config: &SolverConfig) -> Solution {
+    solve_with_stats(problem, config).0
+}
+
+pub fn solve_with_stats(problem: QBF, config: &SolverConfig) -> (Solution, SolveStats) {
+    solve_observed(problem, config, &mut |_, _, _, _| Control::Continue)
+}
+
+pub fn solve_observed(
+        problem: QBF,
+        config: &SolverConfig,
+        observer: &mut dyn Observer) -> (Solution, SolveStats)
+{
+    let (solution, _, stats) = solve_with_witness(problem, config, observer, false);
+    (solution, stats)
+}
+
+/// With `want_witness` set, also returns an assignment to the outermost
+/// block that decides the problem: one that makes the matrix true when the
+/// outermost block is existential and the answer is `Sat`, or one that
+/// makes it false when the block is universal and the answer is `Unsat`.
+pub fn solve_with_witness(
+        problem: QBF,
+        config: &SolverConfig,
+        observer: &mut dyn Observer,
+        want_witness: bool) -> (Solution, Option<Witness>, SolveStats)
+{
+    timed(problem, config, None, observer, want_witness).expect("nothing is written without a trace")
+}
+
+/// `solve_with_witness`, also writing the trace that `trace` asks for. A
+/// trace that can't be written stops the solve.
+pub fn solve_traced(
+        problem: QBF,
+        config: &SolverConfig,
+        trace: &TraceConfig,
+        observer: &mut dyn Observer,
+        want_witness: bool) -> io::Result<(Solution, Option<Witness>, SolveStats)>
+{
+    let mut tracer = Tracer::new(trace)?;
+    timed(problem, config, Some(&mut tracer), observer, want_witness)
+}
+
+fn timed(
+        problem: QBF,
+        config: &SolverConfig,
+        tracer: Option<&mut Tracer>,
+        observer: &mut dyn Observer,
+        want_witness: bool) -> io::Result<(Solution, Option<Witness>, SolveStats)>
+{
+    let start = Instant::now();
+    let mut stats = SolveStats::default();
+    let (solution, witness) = run(problem, config, tracer, observer, want_witness, start, &mut stats)?;
+    stats.total_time = start.elapsed();
+    log!(1, "{:?} after {:.3}s", solution, stats.total_time.as_secs_f64());
+    Ok((solution, witness, stats))
 }
 
-pub fn solve<'r>(problem: QBF) -> Solution {
+// `history[i]` is the expression just before the i-th expansion of the
+// outermost block, so it only depends on that block's first `block - i`
+// variables. Fixing them one at a time, each choice can be checked by
+// evaluating the expression that was current when it was expanded.
+fn extract_witness(aig: &mut Aig, history: &[Lit], target: Lit) -> Vec<(u32, bool)> {
+    let block = history.len() as u32;
+    let mut witness: Vec<(u32, bool)> = vec![];
+
+    for var in 0..block {
+        let mut e = history[(block - 1 - var) as usize];
+        for &(v, value) in &witness {
+            e = substitute(aig, e, v, value);
+        }
+        let value = substitute(aig, e, var, true) == target;
+        witness.push((var, value));
+    }
+
+    witness
+}
+
+// the CSV line for the latest step, which expanded `vars`, and a dump of
+// the graph if one is due
+fn trace_step(
+        tracer: &mut Tracer,
+        step: &ExpansionStep,
+        vars: &[u32],
+        number: usize,
+        problem: (&[Quantifier], &[String], u32),
+        aig: &mut Aig,
+        expr: Lit) -> io::Result<()>
+{
+    let (quantifiers, names, free_inputs) = problem;
+    let var = step.var as usize;
+    let name = names.get(var).cloned().unwrap_or_else(|| var.to_string());
+    tracer.record(number, step, &name, aig.len())?;
+    if !tracer.wants_dump(number, vars) {
+        return Ok(());
+    }
+
+    let (first_quantifier, last_quantifier, blocks) = quantifier_blocks(&quantifiers[..var]);
+    let snapshot = QBF {
+        first_quantifier,
+        last_quantifier,
+        quantifier_blocks: blocks,
+        names: names.iter().take(var).cloned().collect(),
+        free_inputs: free_inputs.min(var as u32),
+        aig: mem::take(aig),
+        expr
+    };
+    let dumped = tracer.dump(number, &snapshot);
+    *aig = snapshot.aig;
+    dumped
+}
+
+fn run(
+        problem: QBF,
+        config: &SolverConfig,
+        mut tracer: Option<&mut Tracer>,
+        observer: &mut dyn Observer,
+        want_witness: bool,
+        start: Instant,
+        stats: &mut SolveStats) -> io::Result<(Solution, Option<Witness>)>
+{
+    let interrupted = || config.interrupted(start);
+    let names = &problem.names;
+    let name = |v: u32| names.get(v as usize).cloned().unwrap_or_else(|| v.to_string());
+
     let n_variables: u32 = problem.quantifier_blocks.iter().sum();
+    let prefix = quantifiers(&problem);
+    let mut aig = problem.aig;
     let mut expr = problem.expr;
 
+    let cone = aig.cone(&[expr]);
+    stats.variables = n_variables;
+    stats.initial_size = cone.len();
+    stats.variables_in_support = cone.iter().filter(|&&n| {
+        matches!(aig.node(Lit::new(n, false)), Node::Var(_))
+    }).count();
+
+    let mut history = vec![];
     let mut current_quantifier = problem.last_quantifier;
     let mut var = n_variables;
-    for block in problem.quantifier_blocks.iter().rev() {
-        for _ in 0..block.clone() {
-            var -= 1;
+    let block_size = config.block_size.clamp(1, MAX_BLOCK_SIZE) as u32;
+    let measure = !config.skip_size || config.max_size.is_some();
+    // the arena's size after the last collection, for when the expression's
+    // own size isn't measured
+    let mut live = aig.len();
+    let outcome = 'expansion: {
+        for (i, &block) in problem.quantifier_blocks.iter().enumerate().rev() {
+            let mut remaining = block;
+            while remaining > 0 {
+                // witnesses need the expression before every variable of the
+                // outermost block
+                let chunk = if want_witness && i == 0 { 1 } else { remaining.min(block_size) };
+                remaining -= chunk;
+                var -= chunk;
 
-            expr = expand(current_quantifier, var, expr);
-            let sz = expr.size();
-            println!("expanded {} {}", var, sz);
+                if want_witness && i == 0 {
+                    history.push(expr);
+                }
+                if let Some(reason) = config.interrupted(start) {
+                    break 'expansion Solution::Unknown(reason);
+                }
+                let step_start = Instant::now();
+                let vars: Vec<u32> = (var..var + chunk).collect();
+                expr =
+                    match expand_block(&mut aig, current_quantifier, &vars, expr, &interrupted) {
+                        Ok(e) => e,
+                        Err(reason) => break 'expansion Solution::Unknown(reason)
+                    };
+                let sz = if measure { Some(aig.size(expr)) } else { None };
+                stats.steps.push(ExpansionStep {
+                    var,
+                    quantifier: current_quantifier,
+                    size: sz,
+                    time: step_start.elapsed()
+                });
+                if aig.len() > GC_FACTOR * sz.unwrap_or(live).max(config.gc_min_nodes) {
+                    let before = aig.len();
+                    let mut roots = vec![expr];
+                    roots.extend_from_slice(&history);
+                    let roots = aig.collect(&roots);
+                    expr = roots[0];
+                    history.copy_from_slice(&roots[1..]);
+                    live = aig.len();
+                    stats.collections += 1;
+                    log!(2, "collected {} of {} nodes", before - aig.len(), before);
+                }
+                if let Some(ref mut tracer) = tracer {
+                    let step = stats.steps.last().unwrap();
+                    let number = stats.steps.len();
+                    trace_step(tracer, step, &vars, number, (&prefix, names, problem.free_inputs), &mut aig, expr)?;
+                }
+                let mem = aig.memory_stats();
+                log!(1, "expanded {} {:?} nodes={} peak={} bytes={}", name(var), sz, mem.nodes, mem.peak_nodes, mem.bytes);
+                if let (Some(max), Some(sz)) = (config.max_size, sz) {
+                    if sz > max {
+                        break 'expansion Solution::Unknown(UnknownReason::SizeLimit);
+                    }
+                }
+                if observer.expanded(var, current_quantifier, sz, start.elapsed()) == Control::Abort {
+                    break 'expansion Solution::Unknown(UnknownReason::Aborted);
+                }
+            }
+            current_quantifier = opposite_quantifier(current_quantifier);
         }
-        current_quantifier = opposite_quantifier(current_quantifier);
-    }
 
-    match *expr {
-        RExp::True => Solution::Sat,
-        RExp::False => Solution::Unsat,
-        _ => panic!("free variable")
-    }
+        match expr {
+            Lit::TRUE => Solution::Sat,
+            Lit::FALSE => Solution::Unsat,
+            _ => panic!("free variable")
+        }
+    };
+
+    let witness =
+        match (problem.first_quantifier, outcome) {
+            (Quantifier::Exists, Solution::Sat) if want_witness =>
+                Some(extract_witness(&mut aig, &history, Lit::TRUE)),
+            (Quantifier::ForAll, Solution::Unsat) if want_witness =>
+                Some(extract_witness(&mut aig, &history, Lit::FALSE)),
+            _ => None
+        };
+
+    stats.memory = aig.memory_stats();
+    stats.simplifications = aig.simplify_stats();
+    Ok((outcome, witness))
 }
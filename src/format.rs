@@ -0,0 +1,68 @@
+use std::io::BufRead;
+use std::path::Path;
+
+use aig::QBF;
+
+use compression::Compression;
+
+use dqdimacs;
+use introduce;
+use printout;
+use qcir;
+use qdimacs;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Format {
+    Native,
+    Qdimacs,
+    // QDIMACS with dependency sets, readable as a QBF when they are nested
+    Dqdimacs,
+    Qcir
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "native" | "qbf" => Some(Format::Native),
+            "qdimacs" | "dimacs" => Some(Format::Qdimacs),
+            "dqdimacs" => Some(Format::Dqdimacs),
+            "qcir" => Some(Format::Qcir),
+            _ => None
+        }
+    }
+
+    /// Guesses from the file extension, looking through any compression
+    /// extension, and falls back to the native format.
+    pub fn from_path(path: &str) -> Format {
+        let mut path = Path::new(path);
+        if Compression::from_path(path) != Compression::None {
+            path = Path::new(path.file_stem().unwrap_or_default());
+        }
+        path.extension()
+            .and_then(|e| e.to_str())
+            .and_then(Format::from_name)
+            .unwrap_or(Format::Native)
+    }
+}
+
+/// Reads a problem a line at a time, so the text never has to be held in
+/// memory all at once.
+pub fn read<R: BufRead>(format: Format, input: R) -> Result<QBF, String> {
+    match format {
+        Format::Native => introduce::read_problem(input),
+        Format::Qdimacs => qdimacs::read(input),
+        Format::Dqdimacs => dqdimacs::read(input)?.into_qbf().map_err(|_| {
+            "the dependency sets are not nested, so this needs the DQBF solver".to_string()
+        }),
+        Format::Qcir => qcir::read(input)
+    }
+}
+
+pub fn write(format: Format, qbf: &QBF) -> String {
+    match format {
+        Format::Native => printout::printout_qbf(qbf),
+        // a QDIMACS prefix is a DQDIMACS prefix too
+        Format::Qdimacs | Format::Dqdimacs => qdimacs::write(qbf),
+        Format::Qcir => qcir::write(qbf)
+    }
+}
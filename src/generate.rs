@@ -0,0 +1,71 @@
+use parser::Expression;
+use parser::Literal;
+use parser::Problem;
+use parser::Statement;
+
+use problem::Quantifier;
+
+/// A small xorshift generator, so that random problems can be reproduced
+/// from a seed.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    pub fn below(&mut self, n: u32) -> u32 {
+        (self.next_u64() % n as u64) as u32
+    }
+
+    pub fn coin(&mut self) -> bool {
+        self.next_u64() & 1 == 1
+    }
+}
+
+/// A random problem over `variables` quantified variables and `statements`
+/// gates, using every kind of statement the parser knows, constants
+/// included.
+pub fn random_problem(rng: &mut Rng, variables: u32, statements: u32) -> Problem {
+    assert!(variables > 0);
+    let mut names = vec![];
+    let mut quantifiers = vec![];
+    for v in 0..variables {
+        let q = if rng.coin() { Quantifier::Exists } else { Quantifier::ForAll };
+        let name = format!("x{}", v);
+        quantifiers.push((q, name.clone()));
+        names.push(name);
+    }
+    let literal = |rng: &mut Rng, names: &[String]| {
+        // favour recent gates, so the result is deep rather than wide
+        let n = names.len() as u32;
+        let i = if rng.coin() { n - 1 - rng.below(n.min(4)) } else { rng.below(n) };
+        Literal { polarity: rng.coin(), var: names[i as usize].clone() }
+    };
+
+    let mut gates = vec![];
+    for g in 0..statements {
+        let exp =
+            match rng.below(12) {
+                0..=3 => Expression::And(literal(rng, &names), literal(rng, &names)),
+                4..=7 => Expression::Or(literal(rng, &names), literal(rng, &names)),
+                8 => Expression::Not(literal(rng, &names)),
+                9 => Expression::Lit(literal(rng, &names)),
+                10 => Expression::True,
+                _ => Expression::False
+            };
+        let name = format!("g{}", g);
+        gates.push(Statement { name: name.clone(), exp });
+        names.push(name);
+    }
+    let output = literal(rng, &names);
+
+    Problem { quantifiers, statements: gates, output }
+}
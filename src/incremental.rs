@@ -0,0 +1,157 @@
+use std::time::Instant;
+
+use aig::Aig;
+use aig::Lit;
+use aig::QBF;
+
+use expand_solve::GC_FACTOR;
+use expand_solve::SolverConfig;
+use expand_solve::eliminate_limited;
+
+use preprocess::quantifiers;
+
+use problem::Quantifier;
+use problem::Solution;
+use problem::UnknownReason;
+
+use solver::Config;
+use solver::Error;
+use solver::Instance;
+use solver::check_bound;
+
+use substitute::copy;
+use substitute::substitute_interruptible;
+
+/// Solves one problem many times, under assumptions that fix variables of
+/// its outermost block and with conjuncts added to the matrix in between.
+///
+/// Assumptions only touch the outermost block, so everything inside it is
+/// expanded once per matrix and the residual is kept for later calls. Only
+/// the residual of the whole matrix is kept: adding a conjunct makes a new
+/// matrix, and its inner blocks are expanded again, though the cofactors of
+/// whatever it shares with the old one are still cached.
+///
+/// The expansion happens in a graph of the solver's own, which is collected
+/// after a solve once it holds much more than the next one needs. The graph
+/// in `aig` only ever holds what the caller builds there, so its literals
+/// stay good for the solver's whole life.
+pub struct IncrementalSolver {
+    qbf: QBF,
+    config: SolverConfig,
+    outer: Vec<(Quantifier, u32)>,
+    inner: Vec<(Quantifier, u32)>,
+    work: Aig,
+    // the matrix that `residual` was computed for, its copy in `work`, and
+    // that copy with every inner block quantified out
+    residual: Option<(Lit, Lit, Lit)>
+}
+
+impl IncrementalSolver {
+    pub fn new(instance: Instance) -> Result<IncrementalSolver, Error> {
+        IncrementalSolver::with_config(instance, &Config::new())
+    }
+
+    /// Of the `config`, only the limits are used, and they apply to each
+    /// call to `solve` separately.
+    pub fn with_config(instance: Instance, config: &Config) -> Result<IncrementalSolver, Error> {
+        let qbf = instance.into_qbf();
+        let config = config.limits().clone();
+        check_bound(&qbf.aig, qbf.expr, qbf.quantifier_blocks.iter().sum())?;
+        let prefix: Vec<(Quantifier, u32)> =
+            quantifiers(&qbf).into_iter().enumerate().map(|(v, q)| (q, v as u32)).collect();
+        let outer_len = qbf.quantifier_blocks.first().map_or(0, |&b| b as usize);
+        Ok(IncrementalSolver {
+            outer: prefix[..outer_len].to_vec(),
+            inner: prefix[outer_len..].to_vec(),
+            qbf,
+            config,
+            work: Aig::new(),
+            residual: None
+        })
+    }
+
+    /// The graph holding the matrix, for building conjuncts in.
+    pub fn aig(&mut self) -> &mut Aig {
+        &mut self.qbf.aig
+    }
+
+    #[doc(hidden)]
+    pub fn qbf(&self) -> &QBF {
+        &self.qbf
+    }
+
+    /// How many nodes the solver's own graph holds.
+    pub fn working_nodes(&self) -> usize {
+        self.work.len()
+    }
+
+    /// Strengthens the matrix for every later call. `lit` may only mention
+    /// variables of the prefix.
+    pub fn add_conjunct(&mut self, lit: Lit) -> Result<(), Error> {
+        check_bound(&self.qbf.aig, lit, self.qbf.quantifier_blocks.iter().sum())?;
+        self.qbf.expr = self.qbf.aig.and(self.qbf.expr, lit);
+        Ok(())
+    }
+
+    fn residual(&mut self, start: Instant) -> Result<Lit, UnknownReason> {
+        let expr = self.qbf.expr;
+        if let Some((matrix, _, r)) = self.residual {
+            if matrix == expr {
+                return Ok(r);
+            }
+        }
+        let copied = copy(&self.qbf.aig, &mut self.work, expr);
+        let r = eliminate_limited(&mut self.work, copied, &self.inner, &self.config, start)?;
+        self.residual = Some((expr, copied, r));
+        Ok(r)
+    }
+
+    // keeps the copy of the matrix, for its cached cofactors, and the
+    // residual; everything built from them under assumptions can go
+    fn collect(&mut self) {
+        let roots: Vec<Lit> =
+            match self.residual {
+                Some((_, copied, r)) => vec![copied, r],
+                None => vec![]
+            };
+        let size = self.work.cone(&roots).len();
+        if self.work.len() > GC_FACTOR * size.max(self.config.gc_min_nodes) {
+            let roots = self.work.collect(&roots);
+            if let Some((matrix, _, _)) = self.residual {
+                self.residual = Some((matrix, roots[0], roots[1]));
+            }
+        }
+    }
+
+    fn decide(&mut self, assumptions: &[(u32, bool)], start: Instant) -> Result<Solution, Error> {
+        let mut e =
+            match self.residual(start) {
+                Ok(e) => e,
+                Err(reason) => return Ok(Solution::Unknown(reason))
+            };
+        let config = &self.config;
+        let interrupted = || config.interrupted(start);
+        for &(var, value) in assumptions {
+            e =
+                match substitute_interruptible(&mut self.work, e, var, value, &interrupted) {
+                    Ok(e) => e,
+                    Err(reason) => return Ok(Solution::Unknown(reason))
+                };
+        }
+        match eliminate_limited(&mut self.work, e, &self.outer, &self.config, start) {
+            Ok(Lit::TRUE) => Ok(Solution::Sat),
+            Ok(Lit::FALSE) => Ok(Solution::Unsat),
+            Ok(e) => Err(Error::FreeVariable(self.work.top_var(e).unwrap())),
+            Err(reason) => Ok(Solution::Unknown(reason))
+        }
+    }
+
+    pub fn solve(&mut self, assumptions: &[(u32, bool)]) -> Result<Solution, Error> {
+        if let Some(&(var, _)) = assumptions.iter().find(|&&(var, _)| var as usize >= self.outer.len()) {
+            return Err(Error::Assumption(self.qbf.name(var)));
+        }
+        let outcome = self.decide(assumptions, Instant::now());
+        self.collect();
+        outcome
+    }
+}
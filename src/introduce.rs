@@ -1,86 +1,240 @@
-use std::rc::Rc;
 use std::collections::HashMap;
+use std::io::BufRead;
 
 use parser;
-use builder::Builder;
 
+use parser::Item;
+use parser::Items;
 use parser::Statement;
 use parser::Expression as PExp;
 
 use problem::Quantifier;
 
-use rc_expression;
-use rc_expression::Exp as Exp;
+use aig;
+use aig::Aig;
+use aig::Lit;
+use aig::Node;
 
-fn lookup_literal(
-        builder: &mut Builder,
-        variables: &HashMap<String, Rc<Exp>>,
-        l: &parser::Literal) -> Rc<Exp>
-{
-    let e = variables.get(&l.var).unwrap().clone();
-    if l.polarity {
-        e
-    } else {
-        builder.not(e)
+use solver::Instance;
+
+use substitute::rename;
+
+// what both builders keep while a problem is put together: the graph, what
+// each name stands for, and the prefix declared so far
+#[derive(Default)]
+struct Declarations {
+    aig: Aig,
+    variables: HashMap<String, Lit>,
+    quantifiers: Vec<Quantifier>,
+    names: Vec<String>
+}
+
+impl Declarations {
+    // binds `name` to the next variable of the prefix
+    fn quantify(&mut self, quantifier: Quantifier, name: String) -> Result<Lit, String> {
+        if self.variables.contains_key(&name) {
+            return Err(format!("{:?} is quantified twice", name));
+        }
+        let e = self.aig.var(self.quantifiers.len() as u32);
+        self.variables.insert(name.clone(), e);
+        self.quantifiers.push(quantifier);
+        self.names.push(name);
+        Ok(e)
+    }
+
+    fn finish(mut self, expr: Lit, free: Vec<String>) -> aig::QBF {
+        let free_inputs = free.len() as u32;
+        let expr = prepend_free(&mut self.aig, expr, free, &mut self.names, &mut self.quantifiers);
+        let (first_quantifier, last_quantifier, blocks) = quantifier_blocks(&self.quantifiers);
+
+        aig::QBF {
+            first_quantifier,
+            last_quantifier,
+            quantifier_blocks: blocks,
+            names: self.names,
+            free_inputs,
+            aig: self.aig,
+            expr
+        }
     }
 }
 
-fn build_statements(
-        builder: &mut Builder,
-        variables: &mut HashMap<String, Rc<Exp>>,
-        statements: &[Statement])
-{
-    for statement in statements {
-        let name = &statement.name;
-        match &statement.exp {
-            &PExp::True => {
-                let e = builder.true_();
-                variables.insert(name.clone(), e);
-            },
-            &PExp::False => {
-                let e = builder.false_();
-                variables.insert(name.clone(), e);
-            },
-            &PExp::Not(ref a) => {
-                let e = lookup_literal(builder, variables, a);
-                let e_ = builder.not(e);
-                variables.insert(name.clone(), e_);
-            },
-            &PExp::And(ref a, ref b) => {
-                let a1 = lookup_literal(builder, variables, a);
-                let b1 = lookup_literal(builder, variables, b);
-                let e = builder.and(a1, b1);
-                variables.insert(name.clone(), e);
-            },
-            &PExp::Or(ref a, ref b) => {
-                let a1 = lookup_literal(builder, variables, a);
-                let b1 = lookup_literal(builder, variables, b);
-                let e = builder.or(a1, b1);
-                variables.insert(name.clone(), e);
-            },
-            &PExp::Lit(ref l) => {
-                let e = lookup_literal(builder, variables, l);
-                variables.insert(name.clone(), e);
+/// Builds a problem one quantifier or statement at a time, so that nothing
+/// but the graph and the name table has to be kept around. Names that are
+/// used without being quantified are free inputs, which end up in an
+/// outermost existential block.
+#[derive(Default)]
+pub struct ProblemBuilder {
+    declarations: Declarations,
+    free: Vec<String>
+}
+
+impl ProblemBuilder {
+    pub fn new() -> ProblemBuilder {
+        ProblemBuilder::default()
+    }
+
+    pub fn quantifier(&mut self, quantifier: Quantifier, name: String) -> Result<(), String> {
+        let d = &mut self.declarations;
+        if d.variables.len() > d.quantifiers.len() {
+            return Err(format!("{:?} is quantified after the first statement", name));
+        }
+        d.quantify(quantifier, name)?;
+        Ok(())
+    }
+
+    fn literal(&mut self, l: &parser::Literal) -> Lit {
+        let d = &mut self.declarations;
+        let e =
+            match d.variables.get(&l.var) {
+                Some(&e) => e,
+                None => {
+                    let e = d.aig.var((d.quantifiers.len() + self.free.len()) as u32);
+                    d.variables.insert(l.var.clone(), e);
+                    self.free.push(l.var.clone());
+                    e
+                }
+            };
+        e.negate_if(!l.polarity)
+    }
+
+    pub fn statement(&mut self, statement: &Statement) -> Result<(), String> {
+        // the uses so far were taken to be a free input
+        if self.declarations.variables.contains_key(&statement.name) && self.free.contains(&statement.name) {
+            return Err(format!("{:?} is defined after it is used", statement.name));
+        }
+        let e =
+            match statement.exp {
+                PExp::True => self.declarations.aig.true_(),
+                PExp::False => self.declarations.aig.false_(),
+                PExp::Not(ref a) => {
+                    let e = self.literal(a);
+                    self.declarations.aig.not(e)
+                },
+                PExp::And(ref a, ref b) => {
+                    let a1 = self.literal(a);
+                    let b1 = self.literal(b);
+                    self.declarations.aig.and(a1, b1)
+                },
+                PExp::Or(ref a, ref b) => {
+                    let a1 = self.literal(a);
+                    let b1 = self.literal(b);
+                    self.declarations.aig.or(a1, b1)
+                },
+                PExp::Lit(ref l) => self.literal(l)
+            };
+        self.declarations.variables.insert(statement.name.clone(), e);
+        Ok(())
+    }
+
+    pub fn finish(mut self, output: &parser::Literal) -> Result<aig::QBF, String> {
+        let e = self.literal(output);
+        Ok(self.declarations.finish(e, self.free))
+    }
+}
+
+/// Builds a problem from Rust code. Variables are declared by name, a
+/// block at a time, and gates are built in `aig()` from the literals that
+/// the declarations return; `build` checks that it all fits together.
+#[derive(Default)]
+pub struct QbfBuilder {
+    declarations: Declarations,
+    output: Option<Lit>
+}
+
+impl QbfBuilder {
+    pub fn new() -> QbfBuilder {
+        QbfBuilder::default()
+    }
+
+    /// Declares the next block of the prefix, inside every earlier one.
+    pub fn block(&mut self, quantifier: Quantifier, names: &[&str]) -> Result<Vec<Lit>, String> {
+        if names.is_empty() {
+            return Err("empty quantifier block".to_string());
+        }
+        // checked up front, so that a bad block declares nothing
+        for (i, &name) in names.iter().enumerate() {
+            if self.declarations.variables.contains_key(name) || names[..i].contains(&name) {
+                return Err(format!("{:?} is quantified twice", name));
+            }
+        }
+        names.iter().map(|&name| self.declarations.quantify(quantifier, name.to_string())).collect()
+    }
+
+    pub fn exists(&mut self, name: &str) -> Result<Lit, String> {
+        Ok(self.block(Quantifier::Exists, &[name])?[0])
+    }
+
+    pub fn forall(&mut self, name: &str) -> Result<Lit, String> {
+        Ok(self.block(Quantifier::ForAll, &[name])?[0])
+    }
+
+    pub fn var(&self, name: &str) -> Result<Lit, String> {
+        self.declarations.variables.get(name).cloned().ok_or_else(|| format!("{:?} is not declared", name))
+    }
+
+    /// The graph to build gates in.
+    pub fn aig(&mut self) -> &mut Aig {
+        &mut self.declarations.aig
+    }
+
+    pub fn output(&mut self, lit: Lit) {
+        self.output = Some(lit);
+    }
+
+    pub fn build(self) -> Result<Instance, String> {
+        let expr = self.output.ok_or_else(|| "no output".to_string())?;
+        let d = self.declarations;
+        let n_variables = d.quantifiers.len() as u32;
+        for n in d.aig.cone(&[expr]) {
+            if let Node::Var(v) = d.aig.node(Lit::new(n, false)) {
+                if v >= n_variables {
+                    return Err(format!("variable {} is used but not declared", v));
+                }
             }
         }
+        Ok(Instance::from(d.finish(expr, vec![])))
     }
 }
 
-fn quantifier_blocks(quantifiers: &[Quantifier]) -> (Quantifier, Quantifier, Vec<u32>) {
-    if quantifiers.len() == 0 {
+/// Free variables are numbered after the prefix as they turn up, but belong
+/// in an existential block in front of it. Renumbers them there, adds them
+/// to `names` and `quantifiers`, and returns the renamed expression. The
+/// caller records how many there were as the `QBF`'s `free_inputs`.
+pub fn prepend_free(
+        aig: &mut Aig,
+        expr: Lit,
+        free: Vec<String>,
+        names: &mut Vec<String>,
+        quantifiers: &mut Vec<Quantifier>) -> Lit
+{
+    if free.is_empty() {
+        return expr;
+    }
+    let bound = quantifiers.len() as u32;
+    let n_free = free.len() as u32;
+    let expr = rename(aig, expr, &|v| if v < bound { v + n_free } else { v - bound });
+    let expr = aig.collect(&[expr])[0];
+    names.splice(0..0, free);
+    quantifiers.splice(0..0, vec![Quantifier::Exists; n_free as usize]);
+    expr
+}
+
+pub fn quantifier_blocks(quantifiers: &[Quantifier]) -> (Quantifier, Quantifier, Vec<u32>) {
+    if quantifiers.is_empty() {
         (Quantifier::Exists, Quantifier::Exists, vec![])
     } else {
-        let first_quantifier = quantifiers[0].clone();
+        let first_quantifier = quantifiers[0];
         let mut output = vec![];
 
-        let mut current_quantifier = first_quantifier.clone();
+        let mut current_quantifier = first_quantifier;
         let mut n = 1;
 
-        for quantifier in &quantifiers[1..] {
-            if quantifier.clone() == current_quantifier {
+        for &quantifier in &quantifiers[1..] {
+            if quantifier == current_quantifier {
                 n += 1;
             } else {
-                current_quantifier = quantifier.clone();
+                current_quantifier = quantifier;
                 output.push(n);
                 n = 1;
             }
@@ -91,24 +245,49 @@ fn quantifier_blocks(quantifiers: &[Quantifier]) -> (Quantifier, Quantifier, Vec
     }
 }
 
-pub fn construct_problem(parsed: parser::Problem) -> rc_expression::QBF {
-    let quantifiers = parsed.quantifiers;
-    let statements = parsed.statements;
-    let output = parsed.output;
-
-    let (quantifiers1, names) : (Vec<_>, Vec<_>) = quantifiers.into_iter().unzip();
-    let variable_expressions = (0..(quantifiers1.len() as u32)).map(|v| Rc::new(Exp::Var(v)));
-    let mut variables: HashMap<_, _> = names.into_iter().zip(variable_expressions).collect();
-
-    let mut builder = Builder::new();
-    build_statements(&mut builder, &mut variables, statements.as_slice());
-    let e = lookup_literal(&mut builder, &mut variables, &output);
-    let (first_quantifier, last_quantifier, blocks) = quantifier_blocks(&quantifiers1.as_slice());
-
-    rc_expression::QBF {
-        first_quantifier: first_quantifier,
-        last_quantifier: last_quantifier,
-        quantifier_blocks: blocks,
-        expr: e
+fn build_problem(parsed: parser::Problem) -> Result<aig::QBF, String> {
+    let mut builder = ProblemBuilder::new();
+    for (quantifier, name) in parsed.quantifiers {
+        builder.quantifier(quantifier, name)?;
+    }
+    for statement in &parsed.statements {
+        builder.statement(statement)?;
+    }
+    builder.finish(&parsed.output)
+}
+
+pub fn construct_problem(parsed: parser::Problem) -> aig::QBF {
+    match build_problem(parsed) {
+        Ok(qbf) => qbf,
+        Err(e) => panic!("{}", e)
+    }
+}
+
+/// Reads a problem in the native format an item at a time, building the
+/// graph as it goes, so that the text is never held in memory.
+pub fn read_problem<R: BufRead>(input: R) -> Result<aig::QBF, String> {
+    let mut builder = ProblemBuilder::new();
+    let mut items = Items::new(input);
+    let mut output = None;
+
+    while let Some((line_no, item)) = items.next_item()? {
+        if output.is_some() {
+            return Err(format!("line {}: text after the output", line_no));
+        }
+        let outcome =
+            match item {
+                Item::Quantifier(q, name) => builder.quantifier(q, name),
+                Item::Statement(statement) => builder.statement(&statement),
+                Item::Output(l) => {
+                    output = Some(l);
+                    Ok(())
+                }
+            };
+        outcome.map_err(|e| format!("line {}: {}", line_no, e))?;
+    }
+
+    match output {
+        Some(output) => builder.finish(&output),
+        None => Err("missing output".to_string())
     }
 }
@@ -1,17 +1,1102 @@
+extern crate bzip2;
+extern crate flate2;
+extern crate xz2;
+
 #[macro_use]
-extern crate nom;
+#[doc(hidden)]
+pub mod log;
 
-pub mod builder;
+#[doc(hidden)]
+pub mod aig;
+#[doc(hidden)]
+pub mod compression;
+#[doc(hidden)]
+pub mod count;
+#[doc(hidden)]
+pub mod dependency;
+#[doc(hidden)]
 pub mod dot;
+#[doc(hidden)]
+pub mod dqbf;
+#[doc(hidden)]
+pub mod dqdimacs;
+#[doc(hidden)]
 pub mod expand_solve;
+#[doc(hidden)]
+pub mod format;
+#[doc(hidden)]
+pub mod generate;
+#[doc(hidden)]
+pub mod incremental;
+#[doc(hidden)]
 pub mod introduce;
-pub mod n_expression;
+#[doc(hidden)]
 pub mod parser;
+#[doc(hidden)]
 pub mod printout;
+#[doc(hidden)]
+pub mod preprocess;
+#[doc(hidden)]
 pub mod problem;
-pub mod rc_expression;
-pub mod rc_substitute;
+#[doc(hidden)]
+pub mod qcir;
+#[doc(hidden)]
+pub mod qdimacs;
+#[doc(hidden)]
+pub mod reference;
+#[doc(hidden)]
+pub mod simulate;
+#[doc(hidden)]
+pub mod solver;
+#[doc(hidden)]
+pub mod stats;
+#[doc(hidden)]
+pub mod substitute;
+#[doc(hidden)]
+pub mod support;
+#[doc(hidden)]
+pub mod trace;
+
+// everything above is the solver's insides; this is what it offers
+pub use aig::Aig;
+pub use aig::Lit;
+pub use count::Natural;
+pub use dqbf::Binding;
+pub use dqbf::DQBF;
+pub use expand_solve::Control;
+pub use expand_solve::Observer;
+pub use format::Format;
+pub use incremental::IncrementalSolver;
+pub use introduce::QbfBuilder;
+pub use problem::Quantifier;
+pub use problem::Solution;
+pub use problem::UnknownReason;
+pub use solver::Config;
+pub use solver::Engine;
+pub use solver::Error;
+pub use solver::Instance;
+pub use solver::SolveOutcome;
+pub use solver::Solver;
+pub use solver::Statistics;
+pub use solver::Trace;
+pub use solver::Value;
+pub use support::VarSet;
+pub use trace::TraceFormat;
 
 #[cfg(test)]
 mod tests {
+    use std::env;
+    use std::fs;
+    use std::io::Read;
+    use std::io::Write;
+    use std::path::Path;
+    use std::process;
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::atomic::Ordering;
+    use std::thread;
+    use std::time::Duration;
+
+    use bzip2::write::BzEncoder;
+    use flate2::write::GzEncoder;
+    use xz2::write::XzEncoder;
+
+    use aig::Aig;
+    use aig::Lit;
+    use aig::QBF;
+    use compression::Compression;
+    use compression::decompress;
+    use count::Natural;
+    use count::count;
+    use dependency::relax_prefix;
+    use dot::Coloring;
+    use dot::DotOptions;
+    use dot::Negation;
+    use dot;
+    use dqbf::Binding;
+    use dqbf::DQBF;
+    use dqbf;
+    use dqdimacs;
+    use expand_solve::Control;
+    use expand_solve::SolverConfig;
+    use expand_solve::eliminate;
+    use expand_solve::eliminate_bound;
+    use expand_solve::solve;
+    use expand_solve::solve_observed;
+    use expand_solve::solve_traced;
+    use expand_solve::solve_with_config;
+    use expand_solve::solve_with_stats;
+    use expand_solve::solve_with_witness;
+    use format::Format;
+    use format;
+    use generate::Rng;
+    use generate::random_problem;
+    use incremental::IncrementalSolver;
+    use introduce::QbfBuilder;
+    use introduce::construct_problem;
+    use introduce::quantifier_blocks;
+    use introduce::read_problem;
+    use parser::Problem;
+    use parser;
+    use preprocess::preprocess;
+    use preprocess::quantifiers;
+    use problem::Quantifier;
+    use problem::Solution;
+    use problem::UnknownReason;
+    use qdimacs;
+    use reference;
+    use simulate::Evaluator;
+    use simulate::eval;
+    use simulate::probably_equivalent;
+    use simulate::random_patterns;
+    use simulate::simulate;
+    use solver::Trace;
+    use solver;
+    use stats;
+    use substitute::cofactor_pair;
+    use substitute::cofactors;
+    use substitute::substitute;
+    use substitute::substitute_interruptible;
+    use support::Supports;
+    use support::cone_of_influence;
+    use support::occurrences;
+    use trace::TraceConfig;
+    use trace::TraceFormat;
+
+    const INSTANCES: u64 = 2000;
+
+    fn problem(seed: u64) -> Problem {
+        let mut rng = Rng::new(seed);
+        let variables = 1 + rng.below(8);
+        let statements = rng.below(30);
+        random_problem(&mut rng, variables, statements)
+    }
+
+    fn qbf(seed: u64) -> QBF {
+        construct_problem(problem(seed))
+    }
+
+    #[test]
+    fn constants() {
+        let mut aig = Aig::new();
+        assert_eq!(aig.true_(), Lit::TRUE);
+        assert_eq!(aig.false_(), Lit::FALSE);
+        let x = aig.var(0);
+        assert_eq!(aig.and(x, aig.false_()), Lit::FALSE);
+        assert_eq!(aig.or(x, aig.true_()), Lit::TRUE);
+    }
+
+    #[test]
+    fn expansion_matches_reference() {
+        for seed in 0..INSTANCES {
+            let expected = reference::solve_problem(&problem(seed));
+            assert_eq!(reference::solve(&qbf(seed)), expected, "seed {}", seed);
+            assert_eq!(solve(qbf(seed)), expected, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn preprocessing_preserves_answer() {
+        for seed in 0..INSTANCES {
+            let expected = reference::solve_problem(&problem(seed));
+            let (simplified, _) = preprocess(qbf(seed));
+            assert_eq!(reference::solve(&simplified), expected, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn formats_round_trip() {
+        for seed in 0..INSTANCES / 4 {
+            let expected = reference::solve_problem(&problem(seed));
+            for &f in &[Format::Native, Format::Qdimacs, Format::Qcir] {
+                let text = format::write(f, &qbf(seed));
+                let read = format::read(f, text.as_bytes()).unwrap();
+                assert_eq!(solve(read), expected, "seed {} {:?}", seed, f);
+            }
+        }
+    }
+
+    #[test]
+    fn witnesses_decide_the_outer_block() {
+        for seed in 0..INSTANCES {
+            let q = qbf(seed);
+            let first_quantifier = q.first_quantifier;
+            let config = SolverConfig { block_size: 1 + seed as usize % 4, ..SolverConfig::default() };
+            let (solution, witness, _) =
+                solve_with_witness(q, &config, &mut |_, _, _, _| Control::Continue, true);
+            let witness =
+                match (first_quantifier, solution, witness) {
+                    (Quantifier::Exists, Solution::Sat, Some(w)) => w,
+                    (Quantifier::ForAll, Solution::Unsat, Some(w)) => w,
+                    (_, _, None) => continue,
+                    _ => panic!("unexpected witness, seed {}", seed)
+                };
+
+            let mut q = qbf(seed);
+            for (v, value) in witness {
+                q.expr = substitute(&mut q.aig, q.expr, v, value);
+            }
+            assert_eq!(reference::solve(&q), solution, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn simulation_matches_eval() {
+        for seed in 0..INSTANCES / 4 {
+            let q = qbf(seed);
+            let n: u32 = q.quantifier_blocks.iter().sum();
+            let patterns = random_patterns(&mut Rng::new(seed), n as usize);
+            let values = simulate(&q.aig, &[q.expr], &patterns)[0];
+            // one evaluator, reused for every assignment and for both roots
+            let mut evaluator = Evaluator::new(&q.aig, &[q.expr, !q.expr]);
+            for i in 0..64 {
+                let assignment: Vec<bool> = patterns.iter().map(|p| p >> i & 1 == 1).collect();
+                assert_eq!(eval(&q.aig, q.expr, &assignment), values >> i & 1 == 1, "seed {}", seed);
+                assert_eq!(evaluator.eval(&assignment), values >> i & 1 == 1, "seed {}", seed);
+            }
+            assert_eq!(evaluator.simulate(&patterns), vec![values, !values], "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn shannon_expansion_is_equivalent() {
+        for seed in 0..INSTANCES / 4 {
+            let mut q = qbf(seed);
+            let x = q.aig.var(0);
+            let f1 = substitute(&mut q.aig, q.expr, 0, true);
+            let f0 = substitute(&mut q.aig, q.expr, 0, false);
+            let a = q.aig.and(x, f1);
+            let b = q.aig.and(!x, f0);
+            let shannon = q.aig.or(a, b);
+            let mut rng = Rng::new(seed);
+            assert!(probably_equivalent(&q.aig, q.expr, shannon, &mut rng, 4), "seed {}", seed);
+            assert!(!probably_equivalent(&q.aig, q.expr, !shannon, &mut rng, 1), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn elimination_leaves_the_outer_prefix() {
+        for seed in 0..INSTANCES {
+            let mut q = qbf(seed);
+            let expected = reference::solve(&q);
+            let prefix: Vec<(Quantifier, u32)> =
+                quantifiers(&q).into_iter().enumerate().map(|(v, q)| (q, v as u32)).collect();
+            let outer = Rng::new(seed).below(prefix.len() as u32 + 1) as usize;
+
+            q.expr = eliminate(&mut q.aig, q.expr, &prefix[outer..]);
+            let outer_quantifiers: Vec<Quantifier> = prefix[..outer].iter().map(|&(q, _)| q).collect();
+            let (first_quantifier, last_quantifier, blocks) = quantifier_blocks(&outer_quantifiers);
+            q.first_quantifier = first_quantifier;
+            q.last_quantifier = last_quantifier;
+            q.quantifier_blocks = blocks;
+            q.names.truncate(outer);
+            assert_eq!(reference::solve(&q), expected, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn undeclared_inputs_are_free() {
+        let problem = parser::parse(b"forall x\ny = or(x, z)\ny\n");
+        let q = construct_problem(problem);
+        assert_eq!(q.names, vec!["z", "x"]);
+        let late = read_problem(&b"exists a\ny = and(a, x)\nx = or(a, a)\ny\n"[..]);
+        assert_eq!(late.unwrap_err(), "line 3: \"x\" is defined after it is used");
+        assert_eq!(q.first_quantifier, Quantifier::Exists);
+        assert_eq!(q.quantifier_blocks, vec![1, 1]);
+        assert_eq!(q.free_inputs, 1);
+
+        // a free input next to a quantified existential, in every format
+        let text = b"exists a\nforall b\nexists c\nx = and(a, i)\ny = and(b, c)\nz = or(x, y)\nz\n";
+        let q = format::read(Format::Native, &text[..]).unwrap();
+        for &f in &[Format::Native, Format::Qdimacs, Format::Qcir] {
+            let mut read = format::read(f, format::write(f, &q).as_bytes()).unwrap();
+            assert_eq!(read.free_inputs, 1, "{:?}", f);
+            if f != Format::Qdimacs {
+                assert_eq!(read.name(0), "i", "{:?}", f);
+            }
+            // a & i | b & c, where c can follow b, and a can be true
+            let residual = eliminate_bound(&mut read);
+            let expected = read.aig.var(0);
+            assert_eq!(residual, expected, "{:?}", f);
+        }
+
+        // QCIR's free block can only come first
+        let late = b"#QCIR-G14\nforall(b)\nfree(i)\noutput(x)\nx = and(b, i)\n";
+        assert_eq!(format::read(Format::Qcir, &late[..]).unwrap_err(), "line 3: free block after quantifiers");
+    }
+
+    #[test]
+    fn reading_native_a_statement_at_a_time() {
+        // the reader and the whole-text parser agree however the items are
+        // spread over lines
+        let layouts: [&[u8]; 3] = [
+            b"forall a\nexists b\nx = and(a, b)\ny = or(x, ~a)\ny\n",
+            b"forall a exists b x = and(a,b) y = or(x, ~a) y",
+            b"forall\n a exists b\nx =\n and(a,\n b) y = or(x,\n~a)\n\n y\n"
+        ];
+        for text in &layouts {
+            let read = read_problem(&text[..]).unwrap();
+            let parsed = construct_problem(parser::parse(text));
+            assert_eq!(read.names, vec!["a", "b"]);
+            assert_eq!(read.quantifier_blocks, parsed.quantifier_blocks);
+            assert_eq!(reference::solve(&read), reference::solve(&parsed));
+        }
+
+        let errors: [(&[u8], &str); 5] = [
+            (b"forall a\nx = and(a,\n", "line 2: unexpected end of input"),
+            (b"forall a\nx = and(a; a)\nx\n", "line 2: unexpected ';'"),
+            (b"forall a\nx = and(a a)\nx\n", "line 2: expected \",\", found \"a\""),
+            (b"forall a\nx = not(a)\nx\n\nforall b\n", "line 5: text after the output"),
+            (b"forall a\n", "missing output")
+        ];
+        for &(text, error) in &errors {
+            assert_eq!(read_problem(text).unwrap_err(), error);
+        }
+        assert!(parser::try_parse(b"forall a\nx = not(a)\nforall b\nx\n").is_err());
+    }
+
+    #[test]
+    fn incremental_matches_reference() {
+        for seed in 0..INSTANCES / 4 {
+            let mut rng = Rng::new(seed);
+            let mut solver = IncrementalSolver::new(qbf(seed).into()).unwrap();
+            let outer = solver.qbf().quantifier_blocks.first().cloned().unwrap_or(0);
+            let n: u32 = solver.qbf().quantifier_blocks.iter().sum();
+
+            let mut q = qbf(seed);
+            for round in 0..4 {
+                if round % 2 == 1 {
+                    let (v, negated, w) = (rng.below(n), rng.coin(), rng.below(n));
+                    let x = solver.aig().var(v).negate_if(negated);
+                    let y = solver.aig().var(w);
+                    let c = solver.aig().or(x, y);
+                    solver.add_conjunct(c).unwrap();
+
+                    let x = q.aig.var(v).negate_if(negated);
+                    let y = q.aig.var(w);
+                    let c = q.aig.or(x, y);
+                    q.expr = q.aig.and(q.expr, c);
+                }
+                let assumptions: Vec<(u32, bool)> = (0..outer)
+                    .map(|v| (v, rng.below(3)))
+                    .filter(|&(_, c)| c < 2)
+                    .map(|(v, c)| (v, c == 1))
+                    .collect();
+
+                let matrix = q.expr;
+                for &(v, value) in &assumptions {
+                    q.expr = substitute(&mut q.aig, q.expr, v, value);
+                }
+                let expected = reference::solve(&q);
+                q.expr = matrix;
+                assert_eq!(solver.solve(&assumptions).unwrap(), expected, "seed {} round {}", seed, round);
+            }
+        }
+    }
+
+    #[test]
+    fn incremental_errors_and_limits() {
+        let q = chain(5, 2000, 12);
+        let mut solver = IncrementalSolver::with_config(q.clone().into(), &solver::Config::new().gc_min_nodes(1 << 6)).unwrap();
+        match solver.solve(&[(4, true)]) {
+            Err(solver::Error::Assumption(ref name)) if name == "x4" => {},
+            o => panic!("{:?}", o)
+        }
+        assert_eq!(solver.solve(&[(4, true)]).unwrap_err().to_string(), "variable \"x4\" is not in the outermost block");
+        let free = solver.aig().var(12);
+        match solver.add_conjunct(free) {
+            Err(solver::Error::FreeVariable(12)) => {},
+            o => panic!("{:?}", o)
+        }
+        let mut open = q.clone();
+        let free = open.aig.var(12);
+        open.expr = open.aig.and(open.expr, free);
+        assert!(IncrementalSolver::new(open.into()).is_err());
+
+        // solving under assumptions is collected after each call, and
+        // leaves the caller's literals alone
+        let all_false: Vec<Lit> = (0..4).map(|v| !solver.aig().var(v)).collect();
+        let c = solver.aig().and_all(&all_false);
+        let mut uncollected = IncrementalSolver::with_config(q.clone().into(), &solver::Config::new().gc_min_nodes(1 << 40)).unwrap();
+        for bits in 0..16 {
+            let assumptions: Vec<(u32, bool)> = (0..4).map(|v| (v, bits >> v & 1 == 1)).collect();
+            let expected = uncollected.solve(&assumptions).unwrap();
+            assert_eq!(solver.solve(&assumptions).unwrap(), expected);
+        }
+        assert!(solver.working_nodes() < uncollected.working_nodes() / 2);
+        solver.add_conjunct(c).unwrap();
+        let mut expected = q.clone();
+        for v in 0..4 {
+            let x = expected.aig.var(v);
+            expected.expr = expected.aig.and(expected.expr, !x);
+        }
+        assert_eq!(solver.solve(&[]).unwrap(), solve(expected));
+        assert_eq!(solver.solve(&[(0, true)]).unwrap(), Solution::Unsat);
+
+        let cancel = Arc::new(AtomicBool::new(true));
+        let limited = [
+            (solver::Config::new().timeout(Duration::ZERO), UnknownReason::Timeout),
+            (solver::Config::new().max_size(1), UnknownReason::SizeLimit),
+            (solver::Config::new().cancel(cancel), UnknownReason::Cancelled)
+        ];
+        for (config, reason) in &limited {
+            let mut solver = IncrementalSolver::with_config(q.clone().into(), config).unwrap();
+            assert_eq!(solver.solve(&[]).unwrap(), Solution::Unknown(*reason));
+        }
+    }
+
+    #[test]
+    fn solver_engines_agree() {
+        let expansion = solver::Solver::new(solver::Config::new().preprocess(true));
+        let reference = solver::Solver::new(solver::Config::new().engine(solver::Engine::Reference));
+        for seed in 0..INSTANCES / 4 {
+            let q = qbf(seed);
+            let expected = reference.solve(q.clone().into()).unwrap().solution;
+            assert_eq!(expansion.solve(q.into()).unwrap().solution, expected, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn queries_through_the_solver() {
+        let solver = solver::Solver::default();
+        for seed in 0..INSTANCES / 4 {
+            let q = qbf(seed);
+            let instance = solver::Instance::from(q.clone());
+            let mut rng = Rng::new(seed);
+            let patterns = random_patterns(&mut rng, instance.variables() as usize);
+            let assignment: Vec<bool> = patterns.iter().map(|p| p & 1 == 1).collect();
+            assert_eq!(instance.eval(&assignment), eval(&q.aig, q.expr, &assignment), "seed {}", seed);
+            assert_eq!(instance.simulate(&patterns), simulate(&q.aig, &[q.expr], &patterns)[0], "seed {}", seed);
+            assert_eq!(instance.support(), Supports::new().support(&q.aig, q.expr).iter().collect::<Vec<u32>>());
+            assert_eq!(solver.count(instance.clone()).unwrap(), count(q.clone()), "seed {}", seed);
+
+            // what is left is a function of the free inputs alone
+            let residual = solver.eliminate(instance).unwrap();
+            assert_eq!(residual.variables(), q.free_inputs, "seed {}", seed);
+            assert_eq!(reference::solve(residual.qbf()), reference::solve(&q), "seed {}", seed);
+        }
+
+        let mut b = QbfBuilder::new();
+        let vars = b.block(Quantifier::Exists, &["a", "b", "c"]).unwrap();
+        let x = b.aig().and(vars[0], vars[2]);
+        let y = b.aig().or(x, vars[1]);
+        b.output(y);
+        let instance = b.build().unwrap();
+        let cone = instance.cone(x);
+        assert_eq!((cone.variables(), cone.name(1)), (2, "c".to_string()));
+
+        let q = chain(4, 500, 12);
+        let mut expansions = 0;
+        let outcome = solver.solve_observed(q.clone().into(), &mut |_, _, _, _| {
+            expansions += 1;
+            Control::Abort
+        }).unwrap();
+        assert_eq!((outcome.solution, expansions), (Solution::Unknown(UnknownReason::Aborted), 1));
+        let reference = solver::Solver::new(solver::Config::new().engine(solver::Engine::Reference));
+        match reference.solve_observed(q.clone().into(), &mut |_, _, _, _| Control::Continue) {
+            Err(solver::Error::Unsupported(_)) => {},
+            o => panic!("{:?}", o)
+        }
+        match solver::Solver::new(solver::Config::new().witness(true)).count(q.clone().into()) {
+            Err(solver::Error::Unsupported(_)) => {},
+            o => panic!("{:?}", o)
+        }
+        match solver::Solver::new(solver::Config::new().timeout(Duration::ZERO)).eliminate(q.into()) {
+            Err(solver::Error::Unknown(UnknownReason::Timeout)) => {},
+            o => panic!("{:?}", o)
+        }
+    }
+
+    #[test]
+    fn preprocessing_statistics() {
+        let solver = solver::Solver::new(solver::Config::new().preprocess(true));
+        let outcome = solver.solve_reader(&b"exists a\nforall b\nexists c\nexists d\nx = and(a, c)\ny = or(x, d)\nz = and(x, y)\nz\n"[..]).unwrap();
+        assert_eq!(outcome.solution, Solution::Sat);
+        let stats = outcome.stats.unwrap();
+        let preprocessing = stats.details().preprocessing.as_ref().unwrap();
+        assert_eq!((preprocessing.units, preprocessing.pure_literals), (2, 0));
+        assert_eq!((preprocessing.variables_before, preprocessing.variables_after), (4, 0));
+        assert!(stats.to_json().contains("\"units\": 2, \"pure_literals\": 0"));
+
+        let solver = solver::Solver::new(solver::Config::new());
+        let stats = solver.solve(qbf(1).into()).unwrap().stats.unwrap();
+        assert!(stats.details().preprocessing.is_none());
+        assert!(stats.to_json().contains("\"preprocessing\": null"));
+    }
+
+    #[test]
+    fn solver_reports_errors() {
+        let solver = solver::Solver::new(solver::Config::new().format(Format::Qdimacs).witness(true));
+        let outcome = solver.solve_reader(&b"p cnf 2 1\ne 2 0\n1 2 0\n"[..]).unwrap();
+        assert_eq!(outcome.solution, Solution::Sat);
+        assert_eq!(outcome.witness.unwrap().len(), 2);
+        match solver.solve_reader(&b"p cnf 1 1\nx 0\n"[..]) {
+            Err(solver::Error::Parse(_)) => {},
+            o => panic!("{:?}", o)
+        }
+
+        let mut q = qbf(0);
+        let n: u32 = q.quantifier_blocks.iter().sum();
+        q.expr = q.aig.var(n);
+        match solver.solve(q.into()) {
+            Err(solver::Error::FreeVariable(v)) => assert_eq!(v, n),
+            o => panic!("{:?}", o)
+        }
+
+        // a witness is for the problem as given
+        let witness = solver::Config::new().witness(true);
+        for config in &[witness.clone().preprocess(true), witness.relax_prefix(true)] {
+            match solver::Solver::new(config.clone()).solve(qbf(0).into()) {
+                Err(solver::Error::Unsupported(_)) => {},
+                o => panic!("{:?}", o)
+            }
+        }
+    }
+
+    #[test]
+    fn building_a_problem() {
+        let mut b = QbfBuilder::new();
+        let x = b.forall("x").unwrap();
+        let y = b.block(Quantifier::Exists, &["y", "z"]).unwrap();
+        assert!(b.exists("x").is_err());
+        assert!(b.block(Quantifier::ForAll, &[]).is_err());
+        assert!(b.block(Quantifier::ForAll, &["w", "w"]).is_err());
+        assert!(b.var("v").is_err());
+
+        // y = ~x is a winning strategy
+        let a = b.aig().and(x, !y[0]);
+        let c = b.aig().and(!x, y[0]);
+        let e = b.aig().or(a, c);
+        b.output(e);
+        let q = b.build().unwrap().into_qbf();
+        assert_eq!(q.names, vec!["x", "y", "z"]);
+        assert_eq!(q.quantifier_blocks, vec![1, 2]);
+        assert_eq!(solve(q), Solution::Sat);
+
+        let mut b = QbfBuilder::new();
+        b.exists("x").unwrap();
+        let undeclared = b.aig().var(1);
+        b.output(undeclared);
+        assert!(b.build().is_err());
+        assert!(QbfBuilder::new().build().is_err());
+    }
+
+    #[test]
+    fn names_survive_the_formats() {
+        let native = format::read(Format::Native, &b"forall v1\nexists g2\nv3 = and(v1, g2)\nv4 = or(v3, ~v1)\nv4\n"[..]).unwrap();
+        let qdimacs = format::read(Format::Qdimacs, &b"p cnf 9 2\na 7 0\ne 3 0\n7 3 0\n-7 -3 0\n"[..]).unwrap();
+        for q in &[native, qdimacs] {
+            let expected = reference::solve(q);
+            let numeric = q.names.iter().all(|n| n.parse::<u32>().is_ok());
+            for &f in &[Format::Native, Format::Qdimacs, Format::Qcir] {
+                let read = format::read(f, format::write(f, q).as_bytes()).unwrap();
+                // QDIMACS only has numbers, and adds the Tseitin variables
+                if f != Format::Qdimacs || numeric {
+                    assert_eq!(read.names[..q.names.len()], q.names[..], "{:?}", f);
+                }
+                assert_eq!(reference::solve(&read), expected, "{:?}", f);
+            }
+        }
+    }
+
+    #[test]
+    fn dot_slices() {
+        let q = qbf(7);
+        let full = dot::printout_qbf(&q);
+        let mut options = DotOptions { negation: Negation::Dashed, depth: Some(1), ..DotOptions::default() };
+        let slice = dot::printout_with(&q, &options);
+        assert!(slice.len() <= full.len());
+        assert!(!slice.contains("\"not\""));
+
+        options.depth = Some(0);
+        let root = dot::printout_with(&q, &options);
+        assert_eq!(root.matches(" -> ").count(), 1);
+
+        // shades stay in range when the support is wider than the prefix
+        let mut open = chain(8, 50, 6);
+        open.quantifier_blocks = vec![1];
+        let options = DotOptions { coloring: Coloring::Support, ..DotOptions::default() };
+        let shaded = dot::printout_with(&open, &options);
+        for shade in shaded.split("fillcolor=gray").skip(1) {
+            let shade: u32 = shade[..shade.find(|c: char| !c.is_ascii_digit()).unwrap()].parse().unwrap();
+            assert!((40..=100).contains(&shade), "{}", shade);
+        }
+        assert!(shaded.contains("fillcolor=gray40"));
+    }
+
+    #[test]
+    fn trace_dumps_reread() {
+        let directory = env::temp_dir().join(format!("qbf-trace-{}", process::id()));
+        for seed in 0..20 {
+            let trace = TraceConfig { directory: directory.clone(), format: TraceFormat::Native, vars: vec![0], every: Some(2) };
+            let (solution, _, stats) = solve_traced(qbf(seed), &SolverConfig::default(), &trace, &mut |_, _, _, _| Control::Continue, false).unwrap();
+
+            let csv = fs::read_to_string(directory.join("sizes.csv")).unwrap();
+            assert_eq!(csv.lines().count(), stats.steps.len() + 1, "seed {}", seed);
+            for entry in fs::read_dir(&directory).unwrap() {
+                let path = entry.unwrap().path();
+                if path.extension().is_some_and(|e| e == "qbf") {
+                    let dumped = format::read(Format::Native, &fs::read(&path).unwrap()[..]).unwrap();
+                    assert_eq!(solve(dumped), solution, "seed {} {:?}", seed, path);
+                }
+            }
+            fs::remove_dir_all(&directory).unwrap();
+        }
+    }
+
+    #[test]
+    fn tracing_through_the_solver() {
+        let directory = env::temp_dir().join(format!("qbf-named-trace-{}", process::id()));
+        let q = chain(6, 200, 8);
+        let trace = Trace::new(&directory).variable("x5").format(TraceFormat::Dot);
+        let solver = solver::Solver::new(solver::Config::new().trace(trace.clone()));
+        let stats = solver.solve(q.clone().into()).unwrap().stats.unwrap();
+
+        let csv = fs::read_to_string(directory.join("sizes.csv")).unwrap();
+        assert_eq!(csv.lines().count(), stats.expansions() + 1);
+        let dumped: Vec<_> = fs::read_dir(&directory).unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|e| e == "dot"))
+            .collect();
+        assert_eq!(dumped.len(), 1);
+        fs::remove_dir_all(&directory).unwrap();
+
+        // x5 is expanded along with x4, x6 and x7
+        let blocks = solver::Config::new().block_size(4).trace(trace.clone());
+        let stats = solver::Solver::new(blocks).solve(q.clone().into()).unwrap().stats.unwrap();
+        assert_eq!(stats.expansions(), 2);
+        assert!(directory.join("step000001.dot").exists());
+        assert!(!directory.join("step000002.dot").exists());
+        fs::remove_dir_all(&directory).unwrap();
+
+        let solver = solver::Solver::new(solver::Config::new().trace(trace.variable("y")));
+        match solver.solve(q.clone().into()) {
+            Err(solver::Error::UnknownVariable(ref name)) if name == "y" => {},
+            o => panic!("{:?}", o)
+        }
+        assert!(!directory.exists());
+
+        // a trace that can't be written is an error, not a warning
+        let file = env::temp_dir().join(format!("qbf-not-a-directory-{}", process::id()));
+        fs::write(&file, "").unwrap();
+        let solver = solver::Solver::new(solver::Config::new().trace(Trace::new(file.join("trace"))));
+        let outcome = solver.solve(q.into());
+        fs::remove_file(&file).unwrap();
+        match outcome {
+            Err(solver::Error::Io(_)) => {},
+            o => panic!("{:?}", o)
+        }
+    }
+
+    #[test]
+    fn block_expansion_matches_reference() {
+        for seed in 0..INSTANCES / 2 {
+            let expected = reference::solve_problem(&problem(seed));
+            for &(block_size, skip_size) in &[(2, false), (3, true), (8, false)] {
+                let config = SolverConfig { block_size, skip_size, ..SolverConfig::default() };
+                let (solution, stats) = solve_with_stats(qbf(seed), &config);
+                assert_eq!(solution, expected, "seed {} block {}", seed, block_size);
+                assert!(stats.steps.iter().all(|s| s.size.is_some() != skip_size));
+            }
+        }
+    }
+
+    #[test]
+    fn cofactors_match_substitution() {
+        for seed in 0..INSTANCES / 4 {
+            let mut q = qbf(seed);
+            let n: u32 = q.quantifier_blocks.iter().sum();
+            for var in 0..n {
+                let f = substitute(&mut q.aig, q.expr, var, false);
+                let t = substitute(&mut q.aig, q.expr, var, true);
+                assert_eq!(cofactor_pair(&mut q.aig, q.expr, var, &|| None), Ok((f, t)), "seed {}", seed);
+            }
+            let vars: Vec<u32> = (0..n.min(3)).collect();
+            let all = cofactors(&mut q.aig, q.expr, &vars, &|| None).unwrap();
+            for (c, &cofactor) in all.iter().enumerate() {
+                let mut e = q.expr;
+                for (i, &v) in vars.iter().enumerate() {
+                    e = substitute(&mut q.aig, e, v, c >> i & 1 == 1);
+                }
+                assert!(probably_equivalent(&q.aig, e, cofactor, &mut Rng::new(seed), 2), "seed {}", seed);
+            }
+        }
+
+        // pairs are kept in the arena, through a collection, so asking again
+        // builds nothing
+        let mut q = chain(9, 300, 8);
+        let (f, t) = cofactor_pair(&mut q.aig, q.expr, 7, &|| None).unwrap();
+        let hits = q.aig.simplify_stats().hash_hits;
+        assert_eq!(cofactor_pair(&mut q.aig, q.expr, 7, &|| None), Ok((f, t)));
+        let roots = q.aig.collect(&[q.expr, f, t]);
+        assert_eq!(cofactor_pair(&mut q.aig, roots[0], 7, &|| None), Ok((roots[1], roots[2])));
+        assert_eq!(q.aig.simplify_stats().hash_hits, hits);
+    }
+
+    #[test]
+    fn supports_and_cones() {
+        for seed in 0..INSTANCES / 4 {
+            let mut q = qbf(seed);
+            let n: u32 = q.quantifier_blocks.iter().sum();
+            let mut supports = Supports::new();
+            let support = supports.support(&q.aig, q.expr).clone();
+            for var in 0..n {
+                let unchanged = substitute(&mut q.aig, q.expr, var, false) == q.expr
+                    && substitute(&mut q.aig, q.expr, var, true) == q.expr;
+                assert_eq!(support.contains(var), !unchanged, "seed {}", seed);
+            }
+            let counts = occurrences(&q.aig, q.expr, n);
+            for (v, o) in counts.iter().enumerate() {
+                assert_eq!(o.even || o.odd, support.contains(v as u32), "seed {}", seed);
+                assert_eq!(o.positive + o.negative > 0, o.even || o.odd, "seed {}", seed);
+            }
+
+            // any node's cone is a problem of its own
+            let cone = q.aig.cone(&[q.expr]);
+            let root = Lit::new(cone[Rng::new(seed).below(cone.len() as u32) as usize], seed % 2 == 1);
+            let mut whole = q.clone();
+            whole.expr = root;
+            let part = cone_of_influence(q.clone(), root);
+            assert_eq!(part.quantifier_blocks.iter().sum::<u32>() as usize, supports.support(&q.aig, root).len());
+            for v in supports.support(&q.aig, root).iter() {
+                assert!(part.variable(&q.name(v)).is_some(), "seed {}", seed);
+            }
+            assert_eq!(reference::solve(&part), reference::solve(&whole), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn dqbf_matches_reference() {
+        for seed in 0..INSTANCES / 4 {
+            let q = qbf(seed);
+            let expected = reference::solve(&q);
+            let mut d = DQBF::from_qbf(q);
+            assert_eq!(dqbf::solve(d.clone()), expected, "seed {}", seed);
+
+            // forget some dependencies, as long as the reference can cope
+            let mut rng = Rng::new(seed);
+            for b in &mut d.bindings {
+                if let Binding::Existential(ref mut deps) = *b {
+                    for u in deps.clone().iter() {
+                        if rng.coin() {
+                            deps.remove(u);
+                        }
+                    }
+                }
+            }
+            let rows: usize = d.bindings.iter().map(|b| {
+                match *b {
+                    Binding::Existential(ref deps) => 1 << deps.len(),
+                    Binding::Universal => 0
+                }
+            }).sum();
+            if rows > 10 {
+                continue;
+            }
+            let expected = reference::solve_dqbf(&d);
+            assert_eq!(dqbf::solve(d.clone()), expected, "seed {}", seed);
+            if let Ok(q) = d.into_qbf() {
+                assert_eq!(reference::solve(&q), expected, "seed {}", seed);
+            }
+        }
+    }
+
+    #[test]
+    fn reading_dqdimacs() {
+        // y may only see x1 but has to equal x2
+        let nested = &b"p cnf 3 2\na 1 2 0\nd 3 1 0\n3 -2 0\n-3 2 0\n"[..];
+        assert_eq!(dqbf::solve(dqdimacs::parse(nested).unwrap()), Solution::Unsat);
+        let q = format::read(Format::Dqdimacs, nested).unwrap();
+        assert_eq!(q.quantifier_blocks, vec![1, 1, 1]);
+        assert_eq!(q.names, vec!["1", "3", "2"]);
+        assert_eq!(reference::solve(&q), Solution::Unsat);
+
+        // two copies of x = y, each seeing only its own universal
+        let crossed = &b"p cnf 4 2\na 1 2 0\nd 3 1 0\nd 4 2 0\n3 -1 0\n-3 1 0\n4 -2 0\n-4 2 0\n"[..];
+        assert_eq!(dqbf::solve(dqdimacs::parse(crossed).unwrap()), Solution::Sat);
+        assert!(format::read(Format::Dqdimacs, crossed).is_err());
+        let solver = solver::Solver::new(solver::Config::new().format(Format::Dqdimacs));
+        assert_eq!(solver.solve_reader(crossed).unwrap().solution, Solution::Sat);
+        let swapped = &b"p cnf 4 2\na 1 2 0\nd 3 2 0\nd 4 1 0\n3 -1 0\n-3 1 0\n4 -2 0\n-4 2 0\n"[..];
+        assert_eq!(solver.solve_reader(swapped).unwrap().solution, Solution::Unsat);
+        // which only has the limits, while a nested one takes everything
+        let witness = solver::Solver::new(solver::Config::new().format(Format::Dqdimacs).witness(true));
+        match witness.solve_reader(crossed) {
+            Err(solver::Error::Unsupported(_)) => {},
+            o => panic!("{:?}", o)
+        }
+        assert!(witness.solve_reader(nested).unwrap().witness.is_some());
+
+        assert!(dqdimacs::parse(&b"p cnf 2 0\ne 1 0\nd 2 1 0\n"[..]).is_err());
+
+        // both readers go through the same prefix and clause handling, and
+        // only DQDIMACS has `d` lines
+        let dependent = &b"p cnf 2 1\na 1 0\nd 2 1 0\n1 2 0\n"[..];
+        assert!(qdimacs::parse(dependent).unwrap_err().starts_with("line 3:"));
+        assert_eq!(dqbf::solve(dqdimacs::parse(dependent).unwrap()), Solution::Sat);
+        let bad = [
+            (&b"p cnf 2 1\ne 1 0\n1 0\ne 2 0\n"[..], "line 4: quantifier after clauses"),
+            (&b"p cnf 2 1\ne 1 1 0\n"[..], "line 2: bad variable 1"),
+            (&b"c\ne 1 0\n"[..], "line 2: expected header before \"e 1 0\"")
+        ];
+        for &(input, error) in &bad {
+            assert_eq!(qdimacs::parse(input).unwrap_err(), error);
+            assert_eq!(dqdimacs::parse(input).unwrap_err(), error);
+        }
+    }
+
+    // random clauses under a random prefix, where there are conjuncts to
+    // tell apart
+    fn cnf(seed: u64) -> QBF {
+        let mut rng = Rng::new(seed);
+        let variables = 2 + rng.below(5);
+        let clauses = 2 + rng.below(10);
+        let mut text = format!("p cnf {} {}\n", variables, clauses);
+        for v in 1..=variables {
+            text.push_str(&format!("{} {} 0\n", if rng.coin() { "a" } else { "e" }, v));
+        }
+        for _ in 0..clauses {
+            for _ in 0..2 + rng.below(2) {
+                let v = 1 + rng.below(variables) as i64;
+                text.push_str(&format!("{} ", if rng.coin() { v } else { -v }));
+            }
+            text.push_str("0\n");
+        }
+        format::read(Format::Qdimacs, text.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn relaxing_the_prefix() {
+        for seed in 0..INSTANCES {
+            for (q, negate) in [(cnf(seed), false), (cnf(seed), true), (qbf(seed), false)] {
+                let mut q = q;
+                if negate {
+                    q.expr = q.aig.not(q.expr);
+                }
+                let relaxed = relax_prefix(q.clone());
+                assert!(relaxed.quantifier_blocks.len() <= q.quantifier_blocks.len(), "seed {}", seed);
+                assert_eq!(relaxed.quantifier_blocks.iter().sum::<u32>(), q.quantifier_blocks.iter().sum::<u32>());
+                assert_eq!(reference::solve(&relaxed), reference::solve(&q), "seed {}", seed);
+            }
+        }
+
+        // y only meets x through a, which comes before x
+        let q = format::read(Format::Qdimacs, &b"p cnf 3 2\ne 1 0\na 2 0\ne 3 0\n1 2 0\n3 -1 0\n"[..]).unwrap();
+        let relaxed = relax_prefix(q);
+        assert_eq!(relaxed.quantifier_blocks, vec![2, 1]);
+        assert_eq!(relaxed.names, vec!["1", "3", "2"]);
+
+        // but here it meets x through e, and has to stay behind it
+        let q = format::read(Format::Qdimacs, &b"p cnf 4 4\ne 4 0\na 1 0\ne 2 3 0\n-1 2 0\n1 -2 0\n-2 3 0\n2 -3 0\n"[..]).unwrap();
+        assert_eq!(relax_prefix(q).quantifier_blocks, vec![1, 1, 2]);
+    }
+
+    #[test]
+    fn counting_the_outer_block() {
+        for seed in 0..INSTANCES / 4 {
+            let q = qbf(seed);
+            let outer = if q.first_quantifier == Quantifier::Exists { q.quantifier_blocks[0] } else { 0 };
+            let mut expected = 0;
+            for assignment in 0..1u64 << outer {
+                let mut fixed = q.clone();
+                for v in 0..outer {
+                    fixed.expr = substitute(&mut fixed.aig, fixed.expr, v, assignment >> v & 1 == 1);
+                }
+                if reference::solve(&fixed) == Solution::Sat {
+                    expected += 1;
+                }
+            }
+            assert_eq!(count(q), Natural::from(expected), "seed {}", seed);
+        }
+
+        // the odd assignments to 100 variables, then those of them where
+        // the last one implies any value of a universal, so is false
+        let mut builder = QbfBuilder::new();
+        let names: Vec<String> = (0..100).map(|i| format!("x{}", i)).collect();
+        let names: Vec<&str> = names.iter().map(|n| &n[..]).collect();
+        let xs = builder.block(Quantifier::Exists, &names).unwrap();
+        let mut parity = Lit::FALSE;
+        for &x in &xs {
+            let aig = builder.aig();
+            let both = aig.and(parity, x);
+            let neither = aig.and(aig.not(parity), aig.not(x));
+            parity = aig.and(aig.not(both), aig.not(neither));
+        }
+        builder.output(parity);
+        let q = builder.build().unwrap().into_qbf();
+        assert_eq!(count(q.clone()).to_string(), "633825300114114700748351602688");
+        let mut q = q;
+        let y = q.aig.var(100);
+        let differ = q.aig.and(xs[99], q.aig.not(y));
+        q.expr = q.aig.and(q.expr, q.aig.not(differ));
+        q.quantifier_blocks.push(1);
+        q.names.push("y".to_string());
+        q.last_quantifier = Quantifier::ForAll;
+        assert_eq!(count(q).to_string(), "316912650057057350374175801344");
+
+        assert_eq!(Natural::power_of_two(100).sub(&Natural::from(1)).to_string(), "1267650600228229401496703205375");
+        assert_eq!(Natural::from(u64::MAX).add(&Natural::from(1)), Natural::power_of_two(64));
+        assert_eq!(Natural::from(3).shl(40), Natural::from(3 << 40));
+    }
+
+    #[test]
+    fn compressed_input() {
+        let text = format::write(Format::Native, &qbf(3));
+        let mut gzip = GzEncoder::new(vec![], flate2::Compression::default());
+        gzip.write_all(text.as_bytes()).unwrap();
+        let mut xz = XzEncoder::new(vec![], 6);
+        xz.write_all(text.as_bytes()).unwrap();
+        let mut bzip2 = BzEncoder::new(vec![], bzip2::Compression::default());
+        bzip2.write_all(text.as_bytes()).unwrap();
+        let encoded = [
+            (Compression::Gzip, gzip.finish().unwrap()),
+            (Compression::Xz, xz.finish().unwrap()),
+            (Compression::Bzip2, bzip2.finish().unwrap()),
+            (Compression::None, text.clone().into_bytes())
+        ];
+        for (compression, bytes) in &encoded {
+            assert_eq!(Compression::detect(bytes), *compression);
+            // the magic bytes win over a misleading name
+            let mut decoded = String::new();
+            decompress(&bytes[..], Some(Path::new("problem.qbf.xz"))).unwrap().read_to_string(&mut decoded).unwrap();
+            assert_eq!(decoded, text, "{:?}", compression);
+        }
+
+        assert_eq!(Compression::detect(b"BZh9"), Compression::Bzip2);
+        assert_eq!(Compression::detect(b"BZh = and(a, b)"), Compression::None);
+        let path = Path::new("problem.qbf.gz");
+        assert_eq!(Compression::guess(b"", Some(path)), Compression::Gzip);
+        assert_eq!(Compression::guess(b"BZh", Some(path)), Compression::Gzip);
+        assert_eq!(Compression::guess(b"BZh", None), Compression::None);
+        assert_eq!(Compression::guess(b"exists a\na\n", Some(path)), Compression::None);
+        assert_eq!(Compression::from_path("a.bz2"), Compression::Bzip2);
+        assert_eq!(Compression::from_path("a.qdimacs"), Compression::None);
+    }
+
+    // `gates` random gates over `variables` variables in alternating blocks
+    // of four, outermost existential, each taking the one before it as an
+    // input, so the graph is as deep as it is large.
+    // Setting the first block true satisfies it.
+    fn chain(seed: u64, gates: usize, variables: u32) -> QBF {
+        let mut rng = Rng::new(seed);
+        let mut aig = Aig::new();
+        let mut recent: Vec<Lit> = (0..variables).map(|v| aig.var(v)).collect();
+        for _ in 0..gates {
+            let n = recent.len() as u32;
+            let a = recent[n as usize - 1].negate_if(rng.coin());
+            let b = recent[(n - 1 - rng.below(n.min(64))) as usize].negate_if(rng.coin());
+            let x = aig.var(rng.below(variables)).negate_if(rng.coin());
+            let e = aig.and(a, b);
+            let e = aig.or(e, x);
+            if !e.is_const() {
+                recent.push(e);
+            }
+        }
+        let xs: Vec<Lit> = (0..4).map(|v| aig.var(v)).collect();
+        let all = aig.and_all(&xs);
+        let expr = aig.or(*recent.last().unwrap(), all);
+        let prefix: Vec<Quantifier> = (0..variables).map(|v| {
+            if v / 4 % 2 == 0 { Quantifier::Exists } else { Quantifier::ForAll }
+        }).collect();
+        let (first_quantifier, last_quantifier, quantifier_blocks) = quantifier_blocks(&prefix);
+        let names = (0..variables).map(|v| format!("x{}", v)).collect();
+        QBF { first_quantifier, last_quantifier, quantifier_blocks, names, free_inputs: 0, aig, expr }
+    }
+
+    #[test]
+    fn collecting_garbage_during_a_solve() {
+        // the witness needs the expression from before every expansion of
+        // the outermost block to survive the collections
+        let mut q = chain(1, 2000, 12);
+        q.quantifier_blocks = vec![12];
+        q.last_quantifier = Quantifier::Exists;
+        let config = SolverConfig { gc_min_nodes: 1 << 6, ..SolverConfig::default() };
+        let (solution, witness, stats) = solve_with_witness(q.clone(), &config, &mut |_, _, _, _| Control::Continue, true);
+        assert_eq!(solution, Solution::Sat);
+        assert!(stats.collections > 1, "{:?}", stats);
+        // at the default threshold a problem this small is never collected
+        let (unchanged, _, stats) = solve_with_witness(q.clone(), &SolverConfig::default(), &mut |_, _, _, _| Control::Continue, true);
+        assert_eq!((unchanged, stats.collections), (solution, 0));
+        let mut e = q.expr;
+        for (v, value) in witness.unwrap() {
+            e = substitute(&mut q.aig, e, v, value);
+        }
+        assert_eq!(e, Lit::TRUE);
+    }
+
+    #[test]
+    fn deep_graphs_on_a_default_stack() {
+        thread::spawn(|| {
+            let mut q = chain(2, 20_000, 4);
+            let depth = stats::problem_stats(&q).depth;
+            assert!(depth >= 20_000, "{}", depth);
+            let text = format::write(Format::Native, &q);
+            let read = format::read(Format::Native, text.as_bytes()).unwrap();
+            assert_eq!(stats::problem_stats(&read).depth, depth);
+            assert!(dot::printout_qbf(&q).len() > text.len());
+            let e = substitute(&mut q.aig, q.expr, 3, true);
+            assert!(!e.is_const());
+            assert_eq!(solve(q), Solution::Sat);
+        }).join().unwrap();
+    }
+
+    #[test]
+    fn limits_stop_the_solve() {
+        let q = chain(3, 5000, 12);
+        let cancel = Arc::new(AtomicBool::new(false));
+        let limited = [
+            (SolverConfig { timeout: Some(Duration::ZERO), ..SolverConfig::default() }, UnknownReason::Timeout),
+            (SolverConfig { max_size: Some(1), ..SolverConfig::default() }, UnknownReason::SizeLimit),
+            (SolverConfig { cancel: Some(cancel.clone()), ..SolverConfig::default() }, UnknownReason::Cancelled)
+        ];
+        // cancelled from the observer, so after the first step
+        let mut steps = 0;
+        let (solution, stats) = solve_observed(q.clone(), &limited[2].0, &mut |_, _, _, _| {
+            steps += 1;
+            cancel.store(true, Ordering::Relaxed);
+            Control::Continue
+        });
+        assert_eq!(solution, Solution::Unknown(UnknownReason::Cancelled));
+        assert_eq!((steps, stats.steps.len()), (1, 1));
+        for (config, reason) in &limited {
+            assert_eq!(solve_with_config(q.clone(), config), Solution::Unknown(*reason));
+            assert_eq!(dqbf::solve_with_config(DQBF::from_qbf(q.clone()), config), Solution::Unknown(*reason));
+        }
+
+        // the reference engine stops for time and cancellation, and refuses
+        // what it can't honour
+        let reference = solver::Config::new().engine(solver::Engine::Reference);
+        let timed = solver::Solver::new(reference.clone().timeout(Duration::ZERO));
+        assert_eq!(timed.solve(q.clone().into()).unwrap().solution, Solution::Unknown(UnknownReason::Timeout));
+        let cancelled = solver::Solver::new(reference.clone().cancel(cancel.clone()));
+        assert_eq!(cancelled.solve(q.clone().into()).unwrap().solution, Solution::Unknown(UnknownReason::Cancelled));
+        for config in &[reference.clone().max_size(1), reference.witness(true)] {
+            match solver::Solver::new(config.clone()).solve(q.clone().into()) {
+                Err(solver::Error::Unsupported(_)) => {},
+                other => panic!("{:?}", other.map(|o| o.solution))
+            }
+        }
+
+        // and in the middle of a traversal, which says why it stopped
+        let mut q = q;
+        let stop = || Some(UnknownReason::Timeout);
+        assert_eq!(cofactor_pair(&mut q.aig, q.expr, 0, &stop), Err(UnknownReason::Timeout));
+        assert_eq!(cofactors(&mut q.aig, q.expr, &[0, 1], &stop), Err(UnknownReason::Timeout));
+        assert_eq!(substitute_interruptible(&mut q.aig, q.expr, 0, true, &stop), Err(UnknownReason::Timeout));
+    }
+
+    #[test]
+    fn observers_can_abort() {
+        let q = chain(4, 500, 12);
+        let mut seen = vec![];
+        let (solution, stats) = solve_observed(q, &SolverConfig::default(), &mut |var, quantifier, size, _| {
+            seen.push((var, quantifier, size));
+            if seen.len() == 3 { Control::Abort } else { Control::Continue }
+        });
+        assert_eq!(solution, Solution::Unknown(UnknownReason::Aborted));
+        // innermost first, told exactly what the statistics record
+        assert_eq!(seen.iter().map(|s| s.0).collect::<Vec<u32>>(), vec![11, 10, 9]);
+        assert!(seen.iter().all(|s| s.1 == Quantifier::Exists));
+        assert_eq!(seen.iter().map(|s| s.2).collect::<Vec<_>>(), stats.steps.iter().map(|s| s.size).collect::<Vec<_>>());
+    }
 }
@@ -0,0 +1,22 @@
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+static VERBOSITY: AtomicUsize = AtomicUsize::new(0);
+
+/// 0 is silent, 1 reports every expansion, 2 adds memory management.
+pub fn set_verbosity(level: usize) {
+    VERBOSITY.store(level, Ordering::Relaxed);
+}
+
+pub fn verbosity() -> usize {
+    VERBOSITY.load(Ordering::Relaxed)
+}
+
+#[macro_export]
+macro_rules! log {
+    ($level:expr, $($arg:tt)*) => {
+        if $crate::log::verbosity() >= $level {
+            eprintln!($($arg)*);
+        }
+    }
+}
@@ -1,30 +1,415 @@
+#[macro_use]
 extern crate qbf;
 
-use qbf::parser;
-use qbf::introduce;
-
+use std::collections::HashSet;
 use std::fs::File;
-use std::io::Read;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
 
-use qbf::problem::Solution;
-use qbf::expand_solve::solve;
+use qbf::Config;
+use qbf::Instance;
+use qbf::Solution;
+use qbf::SolveOutcome;
+use qbf::Solver;
+use qbf::Trace;
+use qbf::TraceFormat;
+use qbf::aig::QBF;
+use qbf::compression::decompress;
+use qbf::count::count;
+use qbf::aig::Lit;
+use qbf::dot;
+use qbf::dot::Coloring;
+use qbf::dot::DotOptions;
+use qbf::dot::Negation;
+use qbf::dqdimacs;
+use qbf::format;
+use qbf::format::Format;
+use qbf::preprocess::preprocess;
+use qbf::stats::problem_stats;
+use qbf::support::cone_of_influence;
 
-fn main() {
-    std::thread::Builder::new().stack_size(8*1024*1024*1024).spawn(|| {
-        let args: Vec<_> = std::env::args().collect();
-        if !args.len() < 2 {
-            panic!("Expected filename");
+// exit codes used by QBFEVAL
+const EXIT_SAT: i32 = 10;
+const EXIT_UNSAT: i32 = 20;
+const EXIT_UNKNOWN: i32 = 0;
+
+const USAGE: &str = "\
+usage: qbf [solve] [-v]... [--witness] [--stats json] [--timeout SECONDS] [--max-size NODES]
+                 [--block VARIABLES] [--no-size] [--preprocess] [--relax]
+                 [--trace DIR [--trace-every STEPS] [--trace-vars NAME,...] [--trace-format native|dot]]
+                 [FILE]
+       qbf convert [--from FORMAT] --to FORMAT [FILE]
+       qbf dot [--from FORMAT] [--negation node|dashed|dot] [--color level|support]
+               [--root NODE] [--depth LEVELS] [FILE]
+       qbf stats [--from FORMAT] [FILE]
+       qbf simplify [--from FORMAT] [--to FORMAT] [FILE]
+       qbf cone [--from FORMAT] [--to FORMAT] --root NODE [FILE]
+       qbf count [--from FORMAT] [FILE]
+
+FORMAT is one of native, qdimacs, dqdimacs or qcir; by default it is guessed
+from the file extension. Only solve takes DQDIMACS whose dependency sets are
+not nested. NODE is a node's index, which dot shows as its id n<NODE>. FILE
+defaults to stdin, as does -.";
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(1);
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Command {
+    Solve,
+    Convert,
+    Dot,
+    Stats,
+    Simplify,
+    // the part of the problem below one node
+    Cone,
+    Count
+}
+
+struct Options {
+    command: Command,
+    filename: String,
+    from: Option<Format>,
+    to: Option<Format>,
+    config: Config,
+    dot: DotOptions,
+    trace: Option<Trace>,
+    verbosity: usize,
+    json_stats: bool
+}
+
+fn flag_value<T: std::str::FromStr>(args: &mut dyn Iterator<Item=String>, flag: &str) -> T {
+    match args.next().map(|v| v.parse()) {
+        Some(Ok(v)) => v,
+        _ => {
+            eprintln!("{} expects a number", flag);
+            usage()
+        }
+    }
+}
+
+fn format_value(args: &mut dyn Iterator<Item=String>) -> Format {
+    match args.next().and_then(|v| Format::from_name(&v)) {
+        Some(f) => f,
+        None => usage()
+    }
+}
+
+fn parse_args() -> Options {
+    let mut args = std::env::args().skip(1).peekable();
+    let command =
+        match args.peek().map(|s| s.as_ref()) {
+            Some("solve") => Some(Command::Solve),
+            Some("convert") => Some(Command::Convert),
+            Some("dot") => Some(Command::Dot),
+            Some("stats") => Some(Command::Stats),
+            Some("simplify") => Some(Command::Simplify),
+            Some("cone") => Some(Command::Cone),
+            Some("count") => Some(Command::Count),
+            _ => None
+        };
+    // a bare filename means solve, as it always has
+    if command.is_some() {
+        args.next();
+    }
+    let command = command.unwrap_or(Command::Solve);
+
+    let mut options = Options {
+        command,
+        filename: "-".to_string(),
+        from: None,
+        to: None,
+        config: Config::new(),
+        dot: DotOptions::default(),
+        trace: None,
+        verbosity: 0,
+        json_stats: false
+    };
+    let mut filename = None;
+
+    while let Some(arg) = args.next() {
+        let solving = command == Command::Solve;
+        match arg.as_ref() {
+            "--from" | "--format" => {
+                options.from = Some(format_value(&mut args));
+            },
+            "--to" if command == Command::Convert || command == Command::Simplify || command == Command::Cone => {
+                options.to = Some(format_value(&mut args));
+            },
+            "--timeout" if solving => {
+                let secs: f64 = flag_value(&mut args, &arg);
+                options.config = options.config.timeout(Duration::from_secs_f64(secs));
+            },
+            "--max-size" if solving => {
+                options.config = options.config.max_size(flag_value(&mut args, &arg));
+            },
+            "--block" if solving => {
+                options.config = options.config.block_size(flag_value(&mut args, &arg));
+            },
+            "--no-size" if solving => {
+                options.config = options.config.skip_size(true);
+            },
+            "--preprocess" if solving => {
+                options.config = options.config.preprocess(true);
+            },
+            "--relax" if solving => {
+                options.config = options.config.relax_prefix(true);
+            },
+            "--stats" if solving => {
+                match args.next().as_ref().map(|s| s.as_ref()) {
+                    Some("json") => options.json_stats = true,
+                    _ => usage()
+                }
+            },
+            "--trace" if solving => {
+                let directory = PathBuf::from(args.next().unwrap_or_else(|| usage()));
+                options.trace = Some(Trace::new(directory));
+            },
+            "--trace-every" if options.trace.is_some() => {
+                let steps: usize = flag_value(&mut args, &arg);
+                if steps == 0 {
+                    eprintln!("{} expects a positive number", arg);
+                    usage();
+                }
+                options.trace = options.trace.take().map(|t| t.every(steps));
+            },
+            "--trace-vars" if options.trace.is_some() => {
+                let names = args.next().unwrap_or_else(|| usage());
+                options.trace = options.trace.take().map(|t| names.split(',').fold(t, |t, name| t.variable(name)));
+            },
+            "--trace-format" if options.trace.is_some() => {
+                let format =
+                    match args.next().as_ref().map(|s| s.as_ref()) {
+                        Some("native") => TraceFormat::Native,
+                        Some("dot") => TraceFormat::Dot,
+                        _ => usage()
+                    };
+                options.trace = options.trace.take().map(|t| t.format(format));
+            },
+            "--witness" if solving => {
+                options.config = options.config.witness(true);
+            },
+            "--negation" if command == Command::Dot => {
+                options.dot.negation =
+                    match args.next().as_ref().map(|s| s.as_ref()) {
+                        Some("node") => Negation::Node,
+                        Some("dashed") => Negation::Dashed,
+                        Some("dot") => Negation::Dot,
+                        _ => usage()
+                    };
+            },
+            "--color" if command == Command::Dot => {
+                options.dot.coloring =
+                    match args.next().as_ref().map(|s| s.as_ref()) {
+                        Some("level") => Coloring::Level,
+                        Some("support") => Coloring::Support,
+                        _ => usage()
+                    };
+            },
+            "--root" if command == Command::Dot || command == Command::Cone => {
+                options.dot.root = Some(Lit::new(flag_value(&mut args, &arg), false));
+            },
+            "--depth" if command == Command::Dot => {
+                options.dot.depth = Some(flag_value(&mut args, &arg));
+            },
+            "-v" | "--verbose" => {
+                options.verbosity += 1;
+            },
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            },
+            _ if filename.is_none() && (arg == "-" || !arg.starts_with('-')) => {
+                filename = Some(arg);
+            },
+            _ => usage()
         }
+    }
+    if command == Command::Convert && options.to.is_none() {
+        usage();
+    }
+    if command == Command::Cone && options.dot.root.is_none() {
+        usage();
+    }
+    if let Some(filename) = filename {
+        options.filename = filename;
+    }
+    options
+}
+
+fn open_input(filename: &str) -> io::Result<Box<dyn BufRead>> {
+    if filename == "-" {
+        decompress(BufReader::new(io::stdin()), None)
+    } else {
+        let f = BufReader::new(File::open(filename)?);
+        decompress(f, Some(Path::new(filename)))
+    }
+}
 
-        let mut f = File::open(&args[1]).unwrap();
-        let mut s = String::new();
-        f.read_to_string(&mut s).unwrap();
-        let parsed = parser::parse(s.as_ref());
-        let qbf = introduce::construct_problem(parsed);
+fn input_format(options: &Options) -> Format {
+    options.from.unwrap_or_else(|| Format::from_path(&options.filename))
+}
 
-        match solve(qbf) {
-            Solution::Sat => println!("sat"),
-            Solution::Unsat => println!("unsat")
+fn check_root(options: &Options, qbf: &QBF) {
+    if options.dot.root.is_some_and(|r| r.node() as usize >= qbf.aig.len()) {
+        eprintln!("qbf: no node {}", options.dot.root.unwrap().node());
+        process::exit(1);
+    }
+}
+
+fn load(options: &Options) -> QBF {
+    let loaded = open_input(&options.filename)
+        .map_err(|e| e.to_string())
+        .and_then(|input| format::read(input_format(options), input));
+    match loaded {
+        Ok(qbf) => {
+            log!(1, "parsed {}", options.filename);
+            qbf
+        },
+        Err(e) => {
+            eprintln!("qbf: {}: {}", options.filename, e);
+            process::exit(1);
         }
-    }).unwrap().join().unwrap();
+    }
+}
+
+fn config(options: &Options) -> Config {
+    let config = options.config.clone();
+    match options.trace {
+        Some(ref trace) => config.trace(trace.clone()),
+        None => config
+    }
+}
+
+fn solve(options: &Options, qbf: QBF) -> i32 {
+    let n_variables: u32 = qbf.quantifier_blocks.iter().sum();
+    let names: Vec<String> = (0..n_variables).map(|v| qbf.name(v)).collect();
+
+    let outcome =
+        match Solver::new(config(options)).solve(Instance::from(qbf)) {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                eprintln!("qbf: {}: {}", options.filename, e);
+                process::exit(1);
+            }
+        };
+    report(options, outcome, numbered(&names))
+}
+
+// DQBFs are read and solved separately, since only the solver takes them;
+// nested ones are solved like any other QBF
+fn solve_dqbf(options: &Options) -> i32 {
+    let loaded = open_input(&options.filename)
+        .map_err(|e| e.to_string())
+        .and_then(dqdimacs::read);
+    let dqbf =
+        match loaded {
+            Ok(dqbf) => dqbf,
+            Err(e) => {
+                eprintln!("qbf: {}: {}", options.filename, e);
+                process::exit(1);
+            }
+        };
+    let dqbf =
+        match dqbf.into_qbf() {
+            Ok(qbf) => return solve(options, qbf),
+            Err(dqbf) => *dqbf
+        };
+    if options.json_stats {
+        eprintln!("qbf: {}: the DQBF solver keeps no statistics", options.filename);
+        process::exit(1);
+    }
+    let numbered = numbered(&dqbf.names);
+
+    let outcome =
+        match Solver::new(config(options)).solve_dqbf(dqbf) {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                eprintln!("qbf: {}: {}", options.filename, e);
+                process::exit(1);
+            }
+        };
+    report(options, outcome, numbered)
+}
+
+// whether the names are QDIMACS numbers, all different, so that witnesses
+// can keep them; otherwise every variable goes by its position in the prefix
+fn numbered(names: &[String]) -> bool {
+    let distinct: HashSet<&String> = names.iter().collect();
+    distinct.len() == names.len() && names.iter().all(|n| n.parse::<i64>().is_ok_and(|v| v > 0))
+}
+
+fn report(options: &Options, outcome: SolveOutcome, numbered: bool) -> i32 {
+    let (result, code) =
+        match outcome.solution {
+            Solution::Sat => (1, EXIT_SAT),
+            Solution::Unsat => (0, EXIT_UNSAT),
+            Solution::Unknown(reason) => {
+                log!(0, "c {}", reason);
+                (-1, EXIT_UNKNOWN)
+            }
+        };
+    println!("s cnf {}", result);
+    for v in outcome.witness.unwrap_or_default() {
+        let lit: i64 = if numbered { v.name.parse().unwrap() } else { v.var as i64 + 1 };
+        println!("V {} 0", if v.value { lit } else { -lit });
+    }
+    // on stderr, so that stdout is only ever the answer
+    if let (true, Some(stats)) = (options.json_stats, outcome.stats) {
+        eprintln!("{}", stats.to_json());
+    }
+    code
+}
+
+fn main() {
+    let options = parse_args();
+    qbf::log::set_verbosity(options.verbosity);
+    if options.command == Command::Solve && input_format(&options) == Format::Dqdimacs {
+        process::exit(solve_dqbf(&options));
+    }
+    let qbf = load(&options);
+
+    let code =
+        match options.command {
+            Command::Solve => solve(&options, qbf),
+            Command::Convert => {
+                print!("{}", format::write(options.to.unwrap(), &qbf));
+                0
+            },
+            Command::Dot => {
+                check_root(&options, &qbf);
+                println!("{}", dot::printout_with(&qbf, &options.dot));
+                0
+            },
+            Command::Stats => {
+                println!("{}", problem_stats(&qbf));
+                0
+            },
+            Command::Simplify => {
+                let (qbf, stats) = preprocess(qbf);
+                log!(1, "{:?}", stats);
+                let to = options.to.unwrap_or_else(|| input_format(&options));
+                print!("{}", format::write(to, &qbf));
+                0
+            },
+            Command::Cone => {
+                check_root(&options, &qbf);
+                let to = options.to.unwrap_or_else(|| input_format(&options));
+                let qbf = cone_of_influence(qbf, options.dot.root.unwrap());
+                print!("{}", format::write(to, &qbf));
+                0
+            },
+            Command::Count => {
+                println!("{}", count(qbf));
+                0
+            }
+        };
+    process::exit(code);
 }
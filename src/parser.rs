@@ -1,8 +1,6 @@
-use nom::eof;
-use nom::alphanumeric;
-use nom::space;
-use nom::multispace;
-use nom::IResult;
+use std::collections::VecDeque;
+use std::io::BufRead;
+use std::io::Lines;
 
 use problem::Quantifier;
 
@@ -35,161 +33,176 @@ pub struct Problem {
     pub output: Literal
 }
 
-fn string_from_slice(slice: &[u8]) -> String {
-    let mut v = Vec::new();
-    v.extend_from_slice(slice);
-    String::from_utf8(v).unwrap()
+/// One quantifier, statement or the output, as `Items` reads them.
+#[derive(Debug)]
+pub enum Item {
+    Quantifier(Quantifier, String),
+    Statement(Statement),
+    Output(Literal)
+}
+
+fn is_name(token: &str) -> bool {
+    !token.is_empty() && token.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// Splits a problem in the native format into items, reading only as many
+/// lines as it needs. Line breaks are whitespace like any other, so items
+/// can share a line or be spread over several.
+pub struct Items<R> {
+    lines: Lines<R>,
+    line_no: usize,
+    // tokens read but not used yet, with their lines
+    pending: VecDeque<(String, usize)>
+}
+
+impl<R: BufRead> Items<R> {
+    pub fn new(input: R) -> Items<R> {
+        Items { lines: input.lines(), line_no: 0, pending: VecDeque::new() }
+    }
+
+    // reads lines until there are more than `i` tokens, or the input ends
+    fn peek(&mut self, i: usize) -> Result<Option<&str>, String> {
+        while self.pending.len() <= i {
+            let line =
+                match self.lines.next() {
+                    Some(line) => line.map_err(|e| e.to_string())?,
+                    None => return Ok(None)
+                };
+            self.line_no += 1;
+            let mut chars = line.chars().peekable();
+            while let Some(c) = chars.next() {
+                if c.is_ascii_alphanumeric() {
+                    let mut name = c.to_string();
+                    while let Some(&c) = chars.peek().filter(|c| c.is_ascii_alphanumeric()) {
+                        name.push(c);
+                        chars.next();
+                    }
+                    self.pending.push_back((name, self.line_no));
+                } else if "~=(),".contains(c) {
+                    self.pending.push_back((c.to_string(), self.line_no));
+                } else if !c.is_whitespace() {
+                    return Err(format!("line {}: unexpected {:?}", self.line_no, c));
+                }
+            }
+        }
+        Ok(Some(&self.pending[i].0))
+    }
+
+    fn next_token(&mut self) -> Result<(String, usize), String> {
+        self.peek(0)?;
+        self.pending.pop_front().ok_or_else(|| format!("line {}: unexpected end of input", self.line_no))
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+        let (token, line_no) = self.next_token()?;
+        if token != expected {
+            return Err(format!("line {}: expected {:?}, found {:?}", line_no, expected, token));
+        }
+        Ok(())
+    }
+
+    fn literal(&mut self) -> Result<Literal, String> {
+        let polarity = self.peek(0)? != Some("~");
+        if !polarity {
+            self.next_token()?;
+        }
+        let (var, line_no) = self.next_token()?;
+        if !is_name(&var) {
+            return Err(format!("line {}: expected a name, found {:?}", line_no, var));
+        }
+        Ok(Literal { polarity, var })
+    }
+
+    fn expression(&mut self) -> Result<Expression, String> {
+        let gate = self.peek(1)? == Some("(");
+        let e =
+            match self.peek(0)? {
+                Some("and") | Some("or") if gate => {
+                    let (op, _) = self.next_token()?;
+                    self.expect("(")?;
+                    let lhs = self.literal()?;
+                    self.expect(",")?;
+                    let rhs = self.literal()?;
+                    if op == "and" { Expression::And(lhs, rhs) } else { Expression::Or(lhs, rhs) }
+                },
+                Some("not") if gate => {
+                    self.next_token()?;
+                    self.expect("(")?;
+                    Expression::Not(self.literal()?)
+                },
+                Some("true") => {
+                    self.next_token()?;
+                    return Ok(Expression::True);
+                },
+                Some("false") => {
+                    self.next_token()?;
+                    return Ok(Expression::False);
+                },
+                _ => return Ok(Expression::Lit(self.literal()?))
+            };
+        self.expect(")")?;
+        Ok(e)
+    }
+
+    /// The next item and the line it starts on, or `None` at the end of the
+    /// input. Errors say which line they are on.
+    pub fn next_item(&mut self) -> Result<Option<(usize, Item)>, String> {
+        let quantifier =
+            match self.peek(0)? {
+                None => return Ok(None),
+                Some("forall") => Some(Quantifier::ForAll),
+                Some("exists") => Some(Quantifier::Exists),
+                Some(_) => None
+            };
+        let line_no = self.pending[0].1;
+        let second = self.peek(1)?.map(|t| t.to_string());
+        let item =
+            match (quantifier, second) {
+                (Some(q), Some(ref name)) if is_name(name) => {
+                    self.next_token()?;
+                    let (name, _) = self.next_token()?;
+                    Item::Quantifier(q, name)
+                },
+                (_, Some(ref eq)) if eq == "=" => {
+                    let (name, _) = self.next_token()?;
+                    if !is_name(&name) {
+                        return Err(format!("line {}: expected a name, found {:?}", line_no, name));
+                    }
+                    self.next_token()?;
+                    Item::Statement(Statement { name, exp: self.expression()? })
+                },
+                _ => Item::Output(self.literal()?)
+            };
+        Ok(Some((line_no, item)))
+    }
 }
 
-named!(positive_literal<&[u8], Literal >,
-    chain!(
-        name: alphanumeric,
-
-        ||{Literal {var: string_from_slice(name), polarity: true}}
-    )
-);
-
-named!(negative_literal<&[u8], Literal >,
-    chain!(
-        tag!("~") ~
-        name: alphanumeric,
-
-        ||{Literal {var: string_from_slice(name), polarity: false}}
-    )
-);
-
-named!(literal<&[u8], Literal >,
-    alt!(
-        positive_literal |
-        negative_literal
-    )
-);
-
-named!(and<&[u8], Expression >,
-    chain!(
-        tag!("and") ~
-        tag!("(") ~
-        lhs: literal ~
-        tag!(",") ~
-        opt!(space) ~
-        rhs: literal ~
-        tag!(")"),
-
-        ||{Expression::And(lhs, rhs)}
-    )
-);
-
-named!(or<&[u8], Expression >,
-    chain!(
-        tag!("or") ~
-        tag!("(") ~
-        lhs: literal ~
-        tag!(",") ~
-        opt!(space) ~
-        rhs: literal ~
-        tag!(")"),
-
-        ||{Expression::Or(lhs, rhs)}
-    )
-);
-
-named!(not<&[u8], Expression >,
-    chain!(
-        tag!("not") ~
-        tag!("(") ~
-        exp: literal ~
-        tag!(")"),
-
-        ||{Expression::Not(exp)}
-    )
-);
-
-named!(true_<&[u8], Expression >,
-    chain!(
-        tag!("true"),
-
-        ||{Expression::True}
-    )
-);
-
-named!(false_<&[u8], Expression >,
-    chain!(
-        tag!("false"),
-
-        ||{Expression::False}
-    )
-);
-
-named!(literal_expr<&[u8], Expression >,
-    chain!(
-        lit: literal,
-        ||{Expression::Lit(lit)}
-    )
-);
-
-named!(expression<&[u8], Expression >,
-    alt!(and | or | not | true_ | false_ | literal_expr)
-);
-
-named!(statement<&[u8], Statement >,
-    chain!(
-        name: alphanumeric ~
-        opt!(space) ~
-        tag!("=") ~
-        opt!(space) ~
-        exp: expression ~
-        opt!(multispace),
-
-        ||{Statement { name: string_from_slice(name), exp: exp }}
-    )
-);
-
-named!(forall_<&[u8], Quantifier >,
-    chain!(
-        tag!("forall"),
-
-        ||{Quantifier::ForAll}
-    )
-);
-
-named!(exists_<&[u8], Quantifier >,
-    chain!(
-        tag!("exists"),
-
-        ||{Quantifier::Exists}
-    )
-);
-
-named!(quantifier<&[u8], (Quantifier, String) >,
-    chain!(
-        quantifier:
-            alt!(
-                forall_ |
-                exists_
-            ) ~
-        multispace ~
-        name: alphanumeric ~
-        multispace,
-
-        || {(quantifier, string_from_slice(name))}
-    )
-);
-
-named!(file<&[u8], Problem >,
-    chain!(
-        quantifiers: many0!(quantifier) ~
-        statements: many0!(statement) ~
-        output: literal ~
-        opt!(multispace) ~
-        eof,
-
-        ||{Problem {quantifiers: quantifiers, statements: statements, output: output}}
-    )
-);
+/// Reads a whole problem, which must have its quantifiers before its
+/// statements and end with the output.
+pub fn try_parse(input: &[u8]) -> Result<Problem, String> {
+    let mut items = Items::new(input);
+    let mut quantifiers = vec![];
+    let mut statements = vec![];
+    while let Some((line_no, item)) = items.next_item()? {
+        match item {
+            Item::Quantifier(q, name) if statements.is_empty() => quantifiers.push((q, name)),
+            Item::Quantifier(_, name) =>
+                return Err(format!("line {}: {:?} is quantified after the first statement", line_no, name)),
+            Item::Statement(statement) => statements.push(statement),
+            Item::Output(output) => {
+                if let Some((line_no, _)) = items.next_item()? {
+                    return Err(format!("line {}: text after the output", line_no));
+                }
+                return Ok(Problem { quantifiers, statements, output });
+            }
+        }
+    }
+    Err("missing output".to_string())
+}
 
 pub fn parse(input: &[u8]) -> Problem {
-    match file(input) {
-        IResult::Done(_, o) => o,
-        o => panic!("failure to parse: {:?}", o)
+    match try_parse(input) {
+        Ok(o) => o,
+        Err(e) => panic!("{}", e)
     }
 }
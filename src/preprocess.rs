@@ -0,0 +1,96 @@
+use std::collections::HashSet;
+
+use aig::Lit;
+use aig::Node;
+use aig::QBF;
+
+use problem::Quantifier;
+use problem::opposite_quantifier;
+
+use substitute::substitute;
+
+use support::cone_of_influence;
+use support::polarities;
+
+#[derive(Debug, Clone, Default)]
+pub struct PreprocessStats {
+    pub variables_before: u32,
+    pub variables_after: u32,
+    pub size_before: usize,
+    pub size_after: usize,
+    pub units: usize,
+    pub pure_literals: usize,
+    pub unused: usize
+}
+
+pub fn quantifiers(qbf: &QBF) -> Vec<Quantifier> {
+    let mut output = vec![];
+    let mut quantifier = qbf.first_quantifier;
+    for &block in &qbf.quantifier_blocks {
+        output.extend((0..block).map(|_| quantifier));
+        quantifier = opposite_quantifier(quantifier);
+    }
+    output
+}
+
+// variables whose value can be fixed without changing the answer
+fn fixed_values(qbf: &QBF, quantifiers: &[Quantifier], stats: &mut PreprocessStats) -> Option<Vec<(u32, bool)>> {
+    let mut values = vec![];
+    let mut fixed = HashSet::new();
+
+    for l in qbf.aig.implied(qbf.expr) {
+        if let Node::Var(v) = qbf.aig.node(l) {
+            if quantifiers[v as usize] == Quantifier::ForAll {
+                return None;
+            }
+            if fixed.insert(v) {
+                values.push((v, !l.is_complemented()));
+                stats.units += 1;
+            }
+        }
+    }
+
+    for (v, (positive, negative)) in polarities(&qbf.aig, qbf.expr) {
+        if positive == negative || fixed.contains(&v) {
+            continue;
+        }
+        let value = positive == (quantifiers[v as usize] == Quantifier::Exists);
+        values.push((v, value));
+        stats.pure_literals += 1;
+    }
+
+    Some(values)
+}
+
+/// Applies unit and pure literal rules until neither fires, then removes
+/// variables that no longer occur and renumbers the rest.
+pub fn preprocess(mut qbf: QBF) -> (QBF, PreprocessStats) {
+    let mut stats = PreprocessStats::default();
+    let quantifiers = quantifiers(&qbf);
+    stats.variables_before = quantifiers.len() as u32;
+    stats.size_before = qbf.aig.size(qbf.expr);
+
+    loop {
+        match fixed_values(&qbf, &quantifiers, &mut stats) {
+            None => {
+                qbf.expr = Lit::FALSE;
+                break;
+            },
+            Some(ref values) if values.is_empty() => break,
+            Some(values) => {
+                for (v, value) in values {
+                    qbf.expr = substitute(&mut qbf.aig, qbf.expr, v, value);
+                }
+            }
+        }
+    }
+
+    let expr = qbf.expr;
+    qbf = cone_of_influence(qbf, expr);
+    let remaining: u32 = qbf.quantifier_blocks.iter().sum();
+    stats.unused = quantifiers.len() - remaining as usize;
+
+    stats.variables_after = remaining;
+    stats.size_after = qbf.aig.size(qbf.expr);
+    (qbf, stats)
+}
@@ -1,72 +1,87 @@
 use std::collections::HashMap;
-use std::rc::Rc;
+use std::collections::HashSet;
 
-use rc_expression::Exp as RExp;
+use aig::Aig;
+use aig::Lit;
+use aig::Node;
+use aig::QBF;
 
-struct ExpPrinter {
-    output: String,
-    next_var: u32,
-    serialized: HashMap<*const (), String>
-}
+use problem::Quantifier;
+use problem::opposite_quantifier;
 
-impl ExpPrinter {
-    fn new() -> ExpPrinter {
-        ExpPrinter {
-            output: String::new(),
-            serialized: HashMap::new(),
-            next_var: 1
-        }
+fn literal(names: &HashMap<u32, String>, lit: Lit) -> String {
+    let name = &names[&lit.node()];
+    if lit.is_complemented() {
+        format!("~{}", name)
+    } else {
+        name.clone()
     }
+}
 
-    fn new_var(&mut self) -> String {
-        let n = self.next_var;
-        self.next_var += 1;
-        format!("v{}", n)
-    }
+// gates are named v1, v2, ..., skipping any name in `taken`
+fn print_matrix(output: &mut String, aig: &Aig, exp: Lit, var_name: &dyn Fn(u32) -> String, taken: &HashSet<String>) {
+    let mut names = HashMap::new();
+    let mut next_var = 1;
 
-    fn build(&mut self, exp: Rc<RExp>) -> String {
-        let expr_ptr = &*exp as *const _ as *const ();
-        match self.serialized.get(&expr_ptr).map(|v| v.clone()) {
-            Some(v) => v.clone(),
-            None => {
-                let outcome =
-                    match *exp {
-                        RExp::And(ref a, ref b) => {
-                            let a1 = self.build(a.clone());
-                            let b1 = self.build(b.clone());
-                            let v = self.new_var();
-                            self.output.push_str(&format!("{} = and({}, {})\n", &v, a1, b1));
-                            v
-                        },
-                        RExp::Not(ref a) => {
-                            let a1 = self.build(a.clone());
-                            let v = self.new_var();
-                            self.output.push_str(&format!("{} = not({})\n", &v, a1));
-                            v
+    for n in aig.cone(&[exp]) {
+        let name =
+            match aig.node(Lit::new(n, false)) {
+                Node::Var(v) => var_name(v),
+                node => {
+                    let mut v = format!("v{}", next_var);
+                    while taken.contains(&v) {
+                        next_var += 1;
+                        v = format!("v{}", next_var);
+                    }
+                    next_var += 1;
+                    match node {
+                        Node::And(a, b) => {
+                            output.push_str(&format!("{} = and({}, {})\n", &v, literal(&names, a), literal(&names, b)));
                         },
-                        RExp::Var(n) =>
-                            format!("arg_{}", n),
-                        RExp::True => {
-                            let v = self.new_var();
-                            self.output.push_str(&format!("{} = true\n", &v));
-                            v
-                        },
-                        RExp::False => {
-                            let v = self.new_var();
-                            self.output.push_str(&format!("{} = true\n", &v));
-                            v
-                        },
-                    };
-                self.serialized.insert(expr_ptr, outcome.clone());
-                outcome
-            }
-        }
+                        _ => {
+                            output.push_str(&format!("{} = false\n", &v));
+                        }
+                    }
+                    v
+                }
+            };
+        names.insert(n, name);
     }
+
+    output.push_str(&literal(&names, exp));
 }
 
-pub fn printout(exp: Rc<RExp>) -> String {
-    let mut printer = ExpPrinter::new();
-    let out = printer.build(exp);
-    printer.output.push_str(&out);
-    printer.output
+pub fn printout(aig: &Aig, exp: Lit) -> String {
+    let mut output = String::new();
+    print_matrix(&mut output, aig, exp, &|v| format!("arg_{}", v), &HashSet::new());
+    output
+}
+
+fn is_name(s: &str) -> bool {
+    let keywords = ["forall", "exists", "and", "or", "not", "true", "false"];
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric()) && !keywords.contains(&s)
+}
+
+/// The whole problem in the native format, as `introduce::read_problem`
+/// reads it, keeping the input names where the format allows.
+pub fn printout_qbf(qbf: &QBF) -> String {
+    let mut output = String::new();
+    let names = qbf.names_for(&is_name);
+
+    // free inputs aren't quantified, so they read back as free
+    let mut quantifier = qbf.first_quantifier;
+    let mut var = 0;
+    for &block in &qbf.quantifier_blocks {
+        let name = if quantifier == Quantifier::Exists { "exists" } else { "forall" };
+        for v in var.max(qbf.free_inputs)..var + block {
+            output.push_str(&format!("{} {}\n", name, names[v as usize]));
+        }
+        var += block;
+        quantifier = opposite_quantifier(quantifier);
+    }
+
+    let taken = names.iter().cloned().collect();
+    print_matrix(&mut output, &qbf.aig, qbf.expr, &|v| names[v as usize].clone(), &taken);
+    output.push('\n');
+    output
 }
@@ -11,8 +11,30 @@ pub fn opposite_quantifier(q: Quantifier) -> Quantifier {
     }
 }
 
-#[derive(Debug)]
+use std::fmt;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum UnknownReason {
+    Timeout,
+    SizeLimit,
+    Cancelled,
+    Aborted
+}
+
+impl fmt::Display for UnknownReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            UnknownReason::Timeout => write!(f, "timeout"),
+            UnknownReason::SizeLimit => write!(f, "size limit"),
+            UnknownReason::Cancelled => write!(f, "cancelled"),
+            UnknownReason::Aborted => write!(f, "aborted by observer")
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum Solution {
     Sat,
-    Unsat
+    Unsat,
+    Unknown(UnknownReason)
 }
@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::io::BufRead;
+
+use aig::Aig;
+use aig::Lit;
+use aig::Node;
+use aig::QBF;
+
+use introduce::quantifier_blocks;
+
+use problem::Quantifier;
+use problem::opposite_quantifier;
+
+fn is_identifier(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// splits "op(a, b, c)" into "op" and its arguments
+fn call(s: &str, line_no: usize) -> Result<(&str, Vec<&str>), String> {
+    let open = s.find('(').ok_or_else(|| format!("line {}: expected '('", line_no))?;
+    if !s.ends_with(')') {
+        return Err(format!("line {}: expected ')'", line_no));
+    }
+    let args = s[open + 1..s.len() - 1].trim();
+    let args =
+        if args.is_empty() {
+            vec![]
+        } else {
+            args.split(',').map(|a| a.trim()).collect()
+        };
+    Ok((s[..open].trim(), args))
+}
+
+fn lookup(names: &HashMap<String, Lit>, lit: &str, line_no: usize) -> Result<Lit, String> {
+    let (name, negated) =
+        if let Some(name) = lit.strip_prefix('-') {
+            (name, true)
+        } else {
+            (lit, false)
+        };
+    match names.get(name) {
+        Some(&l) => Ok(l.negate_if(negated)),
+        None => Err(format!("line {}: undefined {:?}", line_no, name))
+    }
+}
+
+/// Reads QCIR (the QCIR-G14 gate format, also accepting the cleansed form)
+/// a line at a time. A leading `free` block is treated as outermost
+/// existentials, and recorded as the free inputs.
+pub fn read<R: BufRead>(input: R) -> Result<QBF, String> {
+    let mut aig = Aig::new();
+    let mut names: HashMap<String, Lit> = HashMap::new();
+    let mut quantifiers = vec![];
+    let mut variable_names = vec![];
+    let mut free_inputs = 0;
+    let mut output = None;
+
+    for (i, line) in input.lines().enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        let line_no = i + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        match line.find('=') {
+            None => {
+                let (op, args) = call(line, line_no)?;
+                let quantifier =
+                    match op {
+                        "free" if free_inputs == quantifiers.len() => {
+                            free_inputs += args.len();
+                            Quantifier::Exists
+                        },
+                        "free" => return Err(format!("line {}: free block after quantifiers", line_no)),
+                        "exists" => Quantifier::Exists,
+                        "forall" => Quantifier::ForAll,
+                        "output" => {
+                            if args.len() != 1 || output.is_some() {
+                                return Err(format!("line {}: bad output", line_no));
+                            }
+                            output = Some((args[0].to_string(), line_no));
+                            continue;
+                        },
+                        _ => return Err(format!("line {}: unknown statement {:?}", line_no, op))
+                    };
+                if output.is_some() || names.len() > quantifiers.len() {
+                    return Err(format!("line {}: quantifier after gates", line_no));
+                }
+                for name in args {
+                    if !is_identifier(name) || names.contains_key(name) {
+                        return Err(format!("line {}: bad variable {:?}", line_no, name));
+                    }
+                    let v = aig.var(quantifiers.len() as u32);
+                    names.insert(name.to_string(), v);
+                    variable_names.push(name.to_string());
+                    quantifiers.push(quantifier);
+                }
+            },
+            Some(eq) => {
+                let name = line[..eq].trim();
+                if !is_identifier(name) || names.contains_key(name) {
+                    return Err(format!("line {}: bad gate name {:?}", line_no, name));
+                }
+                let (op, args) = call(line[eq + 1..].trim(), line_no)?;
+                let args = args.iter()
+                    .map(|a| lookup(&names, a, line_no))
+                    .collect::<Result<Vec<Lit>, String>>()?;
+                let e =
+                    match (op, args.len()) {
+                        ("and", _) => aig.and_all(&args),
+                        ("or", _) => aig.or_all(&args),
+                        ("xor", 2) => {
+                            let x = aig.and(args[0], !args[1]);
+                            let y = aig.and(!args[0], args[1]);
+                            aig.or(x, y)
+                        },
+                        ("ite", 3) => {
+                            let x = aig.and(args[0], args[1]);
+                            let y = aig.and(!args[0], args[2]);
+                            aig.or(x, y)
+                        },
+                        _ => return Err(format!("line {}: unknown gate {}/{}", line_no, op, args.len()))
+                    };
+                names.insert(name.to_string(), e);
+            }
+        }
+    }
+
+    let (output, line_no) = output.ok_or_else(|| "missing output".to_string())?;
+    let expr = lookup(&names, &output, line_no)?;
+    let (first_quantifier, last_quantifier, blocks) = quantifier_blocks(&quantifiers);
+    Ok(QBF {
+        first_quantifier,
+        last_quantifier,
+        quantifier_blocks: blocks,
+        names: variable_names,
+        free_inputs: free_inputs as u32,
+        aig,
+        expr
+    })
+}
+
+pub fn parse(input: &[u8]) -> Result<QBF, String> {
+    read(input)
+}
+
+pub fn write(qbf: &QBF) -> String {
+    let mut output = String::from("#QCIR-G14\n");
+
+    let variable_names = qbf.names_for(&is_identifier);
+    let mut quantifier = qbf.first_quantifier;
+    let mut var = 0;
+    if qbf.free_inputs > 0 {
+        let vars: Vec<String> = variable_names[..qbf.free_inputs as usize].to_vec();
+        output.push_str(&format!("free({})\n", vars.join(", ")));
+    }
+    for &block in &qbf.quantifier_blocks {
+        let start = var.max(qbf.free_inputs);
+        let vars: Vec<String> = (start..var + block).map(|v| variable_names[v as usize].clone()).collect();
+        let name = if quantifier == Quantifier::Exists { "exists" } else { "forall" };
+        if !vars.is_empty() {
+            output.push_str(&format!("{}({})\n", name, vars.join(", ")));
+        }
+        var += block;
+        quantifier = opposite_quantifier(quantifier);
+    }
+
+    // gates are named after their node, with underscores in front if that
+    // is already a variable's name
+    let taken: HashSet<&String> = variable_names.iter().collect();
+    let gate_name = |n: u32| {
+        let mut g = format!("g{}", n);
+        while taken.contains(&g) {
+            g.insert(0, '_');
+        }
+        g
+    };
+    let mut names: HashMap<u32, String> = HashMap::new();
+    let mut gates = String::new();
+    let literal = |names: &HashMap<u32, String>, l: Lit| {
+        format!("{}{}", if l.is_complemented() { "-" } else { "" }, names[&l.node()])
+    };
+    for n in qbf.aig.cone(&[qbf.expr]) {
+        let name =
+            match qbf.aig.node(Lit::new(n, false)) {
+                Node::Var(v) => variable_names[v as usize].clone(),
+                Node::And(a, b) => {
+                    let g = gate_name(n);
+                    gates.push_str(&format!("{} = and({}, {})\n", g, literal(&names, a), literal(&names, b)));
+                    g
+                },
+                Node::False => {
+                    let g = gate_name(0);
+                    gates.push_str(&format!("{} = or()\n", g));
+                    g
+                }
+            };
+        names.insert(n, name);
+    }
+
+    output.push_str(&format!("output({})\n", literal(&names, qbf.expr)));
+    output.push_str(&gates);
+    output
+}
@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+use std::io::BufRead;
+
+use aig::Aig;
+use aig::Lit;
+use aig::Node;
+use aig::QBF;
+
+use introduce::prepend_free;
+use introduce::quantifier_blocks;
+
+use problem::Quantifier;
+use problem::opposite_quantifier;
+
+pub fn parse_ints(line: &str, line_no: usize) -> Result<Vec<i64>, String> {
+    line.split_whitespace()
+        .map(|t| t.parse().map_err(|_| format!("line {}: expected a number, found {:?}", line_no, t)))
+        .collect()
+}
+
+/// What a reader of the QDIMACS family makes of its prefix: `declare` gets
+/// each prefix line, by its first letter and without the closing 0, and
+/// `variable` says which variable a number in a clause stands for.
+pub trait Prefix {
+    fn declare(&mut self, tag: char, vars: &[i64]) -> Result<(), String>;
+    fn variable(&mut self, number: i64) -> u32;
+}
+
+/// Reads the header, the prefix lines whose letters are in `tags`, and the
+/// clauses, a line at a time, and gives the graph and its matrix.
+pub fn read_with<R: BufRead>(input: R, tags: &str, prefix: &mut dyn Prefix) -> Result<(Aig, Lit), String> {
+    let mut header = None;
+    let mut aig = Aig::new();
+    let mut conjuncts = vec![];
+    let mut clause = vec![];
+
+    for (i, line) in input.lines().enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        let line_no = i + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('c') {
+            continue;
+        }
+        if line.starts_with('p') {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() != 4 || fields[1] != "cnf" {
+                return Err(format!("line {}: malformed header", line_no));
+            }
+            let ints = parse_ints(&fields[2..].join(" "), line_no)?;
+            header = Some((ints[0], ints[1]));
+            continue;
+        }
+        if header.is_none() {
+            return Err(format!("line {}: expected header before {:?}", line_no, line));
+        }
+
+        match line.chars().next().filter(|&c| tags.contains(c)) {
+            Some(tag) => {
+                if !conjuncts.is_empty() || !clause.is_empty() {
+                    return Err(format!("line {}: quantifier after clauses", line_no));
+                }
+                let vars = parse_ints(&line[1..], line_no)?;
+                if vars.last() != Some(&0) {
+                    return Err(format!("line {}: quantifier block is not terminated by 0", line_no));
+                }
+                prefix.declare(tag, &vars[..vars.len() - 1]).map_err(|e| format!("line {}: {}", line_no, e))?;
+            },
+            None => {
+                for l in parse_ints(line, line_no)? {
+                    if l == 0 {
+                        conjuncts.push(aig.or_all(&clause));
+                        clause.clear();
+                        continue;
+                    }
+                    let v = aig.var(prefix.variable(l.abs()));
+                    clause.push(v.negate_if(l < 0));
+                }
+            }
+        }
+    }
+    if !clause.is_empty() {
+        conjuncts.push(aig.or_all(&clause));
+    }
+    let expr = aig.and_all(&conjuncts);
+    Ok((aig, expr))
+}
+
+// a prefix of `a` and `e` lines; numbers it doesn't bind are free, and
+// numbered after it until they are moved in front
+#[derive(Default)]
+struct Quantified {
+    numbering: HashMap<i64, u32>,
+    quantifiers: Vec<Quantifier>,
+    names: Vec<String>,
+    free: Vec<i64>
+}
+
+impl Prefix for Quantified {
+    fn declare(&mut self, tag: char, vars: &[i64]) -> Result<(), String> {
+        let q = if tag == 'a' { Quantifier::ForAll } else { Quantifier::Exists };
+        for &v in vars {
+            if v <= 0 || self.numbering.contains_key(&v) {
+                return Err(format!("bad variable {}", v));
+            }
+            self.numbering.insert(v, self.quantifiers.len() as u32);
+            self.quantifiers.push(q);
+            self.names.push(v.to_string());
+        }
+        Ok(())
+    }
+
+    fn variable(&mut self, number: i64) -> u32 {
+        let bound = self.quantifiers.len() as u32;
+        let free = &mut self.free;
+        *self.numbering.entry(number).or_insert_with(|| {
+            free.push(number);
+            bound + free.len() as u32 - 1
+        })
+    }
+}
+
+/// Reads QDIMACS a line at a time. Variables that are not bound by the
+/// prefix are treated as existentials in an extra outermost block.
+pub fn read<R: BufRead>(input: R) -> Result<QBF, String> {
+    let mut prefix = Quantified::default();
+    let (mut aig, expr) = read_with(input, "ae", &mut prefix)?;
+    let Quantified { mut quantifiers, mut names, free, .. } = prefix;
+
+    let free_inputs = free.len() as u32;
+    let free = free.iter().map(|v| v.to_string()).collect();
+    let expr = prepend_free(&mut aig, expr, free, &mut names, &mut quantifiers);
+    let (first_quantifier, last_quantifier, blocks) = quantifier_blocks(&quantifiers);
+
+    Ok(QBF {
+        first_quantifier,
+        last_quantifier,
+        quantifier_blocks: blocks,
+        names,
+        free_inputs,
+        aig,
+        expr
+    })
+}
+
+pub fn parse(input: &[u8]) -> Result<QBF, String> {
+    read(input)
+}
+
+/// Writes QDIMACS, Tseitin-encoding every and gate with a fresh existential
+/// variable in the innermost block.
+pub fn write(qbf: &QBF) -> String {
+    // keep the numbers from the input if there were any
+    let variable_numbers: Vec<i64> = qbf.names_for(&|n| n.parse::<i64>().is_ok_and(|v| v > 0))
+        .iter()
+        .enumerate()
+        .map(|(v, n)| n.parse().unwrap_or(v as i64 + 1))
+        .collect();
+    let mut numbers: HashMap<u32, i64> = HashMap::new();
+    let mut gates = vec![];
+    let mut clauses = vec![];
+
+    let cone = qbf.aig.cone(&[qbf.expr]);
+    let mut next = variable_numbers.iter().max().map_or(1, |&m| m + 1);
+    for &n in &cone {
+        match qbf.aig.node(Lit::new(n, false)) {
+            Node::Var(v) => {
+                numbers.insert(n, variable_numbers[v as usize]);
+            },
+            Node::And(a, b) => {
+                let g = next;
+                next += 1;
+                numbers.insert(n, g);
+                gates.push(g);
+                let a1 = numbers[&a.node()] * if a.is_complemented() { -1 } else { 1 };
+                let b1 = numbers[&b.node()] * if b.is_complemented() { -1 } else { 1 };
+                clauses.push(format!("{} {} 0", -g, a1));
+                clauses.push(format!("{} {} 0", -g, b1));
+                clauses.push(format!("{} {} {} 0", g, -a1, -b1));
+            },
+            Node::False => {}
+        }
+    }
+
+    match qbf.expr {
+        Lit::TRUE => {},
+        Lit::FALSE => clauses.push("0".to_string()),
+        e => {
+            let l = numbers[&e.node()];
+            clauses.push(format!("{} 0", if e.is_complemented() { -l } else { l }));
+        }
+    }
+
+    // free inputs stay out of the prefix, so they read back as free
+    let mut blocks = vec![];
+    let mut quantifier = qbf.first_quantifier;
+    let mut var = 0;
+    for &block in &qbf.quantifier_blocks {
+        let start = var.max(qbf.free_inputs as usize);
+        let vars: Vec<String> = variable_numbers[start..var + block as usize].iter().map(|v| v.to_string()).collect();
+        if !vars.is_empty() {
+            blocks.push((quantifier, vars));
+        }
+        var += block as usize;
+        quantifier = opposite_quantifier(quantifier);
+    }
+    if !gates.is_empty() {
+        let gates = gates.iter().map(|g| g.to_string());
+        match blocks.last_mut() {
+            Some(&mut (Quantifier::Exists, ref mut vars)) => vars.extend(gates),
+            _ => blocks.push((Quantifier::Exists, gates.collect()))
+        }
+    }
+
+    let mut output = format!("p cnf {} {}\n", next - 1, clauses.len());
+    for (q, vars) in blocks {
+        let tag = if q == Quantifier::Exists { "e" } else { "a" };
+        output.push_str(&format!("{} {} 0\n", tag, vars.join(" ")));
+    }
+    for clause in clauses {
+        output.push_str(&clause);
+        output.push('\n');
+    }
+    output
+}
@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+
+use aig::QBF;
+
+use dqbf::Binding;
+use dqbf::DQBF;
+
+use parser;
+use parser::Expression;
+
+use preprocess::quantifiers;
+
+use problem::Quantifier;
+use problem::Solution;
+use problem::UnknownReason;
+
+use simulate::Evaluator;
+
+// tries every assignment, in prefix order, unless the matrix gives up
+fn quantify(
+        quantifiers: &[Quantifier],
+        assignment: &mut Vec<bool>,
+        matrix: &mut dyn FnMut(&[bool]) -> Result<bool, UnknownReason>) -> Result<bool, UnknownReason>
+{
+    let i = assignment.len();
+    if i == quantifiers.len() {
+        return matrix(assignment);
+    }
+
+    let mut values = [false, false];
+    for (j, &value) in [false, true].iter().enumerate() {
+        assignment.push(value);
+        values[j] = quantify(quantifiers, assignment, matrix)?;
+        assignment.pop();
+    }
+    Ok(
+        match quantifiers[i] {
+            Quantifier::Exists => values[0] || values[1],
+            Quantifier::ForAll => values[0] && values[1]
+        })
+}
+
+fn solution(value: Result<bool, UnknownReason>) -> Solution {
+    match value {
+        Ok(true) => Solution::Sat,
+        Ok(false) => Solution::Unsat,
+        Err(reason) => Solution::Unknown(reason)
+    }
+}
+
+/// Evaluates the graph under every assignment. Exponential in the number of
+/// variables, so only for checking the real solver on small problems.
+pub fn solve(qbf: &QBF) -> Solution {
+    solve_interruptible(qbf, &|| None)
+}
+
+/// Like `solve`, but asks `interrupted` before every assignment and gives
+/// up with its reason.
+pub fn solve_interruptible(qbf: &QBF, interrupted: &dyn Fn() -> Option<UnknownReason>) -> Solution {
+    let mut evaluator = Evaluator::new(&qbf.aig, &[qbf.expr]);
+    solution(quantify(&quantifiers(qbf), &mut vec![], &mut |assignment| {
+        match interrupted() {
+            Some(reason) => Err(reason),
+            None => Ok(evaluator.eval(assignment))
+        }
+    }))
+}
+
+/// Like `solve`, but interprets the parsed statements directly rather than
+/// going through the graph builder, so it also checks the builder.
+pub fn solve_problem(problem: &parser::Problem) -> Solution {
+    let quantifiers: Vec<Quantifier> = problem.quantifiers.iter().map(|&(q, _)| q).collect();
+    let literal = |values: &HashMap<&str, bool>, l: &parser::Literal| values[&*l.var] == l.polarity;
+
+    solution(quantify(&quantifiers, &mut vec![], &mut |assignment| {
+        let mut values: HashMap<&str, bool> = HashMap::new();
+        for ((_, name), &value) in problem.quantifiers.iter().zip(assignment) {
+            values.insert(name, value);
+        }
+        for statement in &problem.statements {
+            let v =
+                match statement.exp {
+                    Expression::And(ref a, ref b) => literal(&values, a) && literal(&values, b),
+                    Expression::Or(ref a, ref b) => literal(&values, a) || literal(&values, b),
+                    Expression::Not(ref a) => !literal(&values, a),
+                    Expression::Lit(ref a) => literal(&values, a),
+                    Expression::True => true,
+                    Expression::False => false
+                };
+            values.insert(&statement.name, v);
+        }
+        Ok(literal(&values, &problem.output))
+    }))
+}
+
+// picks a truth table for every existential in turn, then checks them
+// against every assignment to the universals
+fn skolem(
+        dqbf: &DQBF,
+        evaluator: &mut Evaluator,
+        universals: &[u32],
+        existentials: &[(u32, Vec<u32>)],
+        tables: &mut Vec<u64>) -> bool
+{
+    let i = tables.len();
+    if i == existentials.len() {
+        let mut assignment = vec![false; dqbf.bindings.len()];
+        return (0..1u64 << universals.len()).all(|values| {
+            for (j, &u) in universals.iter().enumerate() {
+                assignment[u as usize] = values >> j & 1 == 1;
+            }
+            for (&(e, ref deps), &table) in existentials.iter().zip(tables.iter()) {
+                let row = deps.iter().enumerate().fold(0, |row, (j, &u)| row | (assignment[u as usize] as u64) << j);
+                assignment[e as usize] = table >> row & 1 == 1;
+            }
+            evaluator.eval(&assignment)
+        });
+    }
+
+    let rows = 1u64 << existentials[i].1.len();
+    (0..1u64 << rows).any(|table| {
+        tables.push(table);
+        let satisfied = skolem(dqbf, evaluator, universals, existentials, tables);
+        tables.pop();
+        satisfied
+    })
+}
+
+/// Tries every choice of Skolem functions, as truth tables over the
+/// universals each existential depends on. Doubly exponential, so only for
+/// the tiniest problems; no existential may depend on more than five.
+pub fn solve_dqbf(dqbf: &DQBF) -> Solution {
+    let mut universals = vec![];
+    let mut existentials = vec![];
+    for (v, b) in dqbf.bindings.iter().enumerate() {
+        match *b {
+            Binding::Universal => universals.push(v as u32),
+            Binding::Existential(ref deps) => {
+                assert!(deps.len() <= 5);
+                existentials.push((v as u32, deps.iter().collect()));
+            }
+        }
+    }
+    let mut evaluator = Evaluator::new(&dqbf.aig, &[dqbf.expr]);
+    solution(Ok(skolem(dqbf, &mut evaluator, &universals, &existentials, &mut vec![])))
+}
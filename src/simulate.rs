@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+
+use aig::Aig;
+use aig::Lit;
+use aig::Node;
+
+use generate::Rng;
+
+// a node of a flattened cone, with its inputs as positions earlier in it
+// and whether they are complemented
+#[derive(Debug, Copy, Clone)]
+enum Gate {
+    False,
+    Var(u32),
+    And((usize, bool), (usize, bool))
+}
+
+/// The cone of some roots, flattened once into topological order so that
+/// it can be evaluated under many assignments at the cost of the cone
+/// alone, however big the rest of the graph is.
+#[derive(Debug, Clone)]
+pub struct Evaluator {
+    gates: Vec<Gate>,
+    roots: Vec<(usize, bool)>,
+    values: Vec<u64>
+}
+
+impl Evaluator {
+    pub fn new(aig: &Aig, roots: &[Lit]) -> Evaluator {
+        let cone = aig.cone(roots);
+        let position: HashMap<u32, usize> = cone.iter().enumerate().map(|(i, &n)| (n, i)).collect();
+        let input = |l: Lit| (position[&l.node()], l.is_complemented());
+        let gates = cone.iter().map(|&n| {
+            match aig.node(Lit::new(n, false)) {
+                Node::And(a, b) => Gate::And(input(a), input(b)),
+                Node::Var(v) => Gate::Var(v),
+                Node::False => Gate::False
+            }
+        }).collect();
+        Evaluator {
+            gates,
+            roots: roots.iter().map(|&r| input(r)).collect(),
+            values: vec![0; cone.len()]
+        }
+    }
+
+    fn run(&mut self, pattern: &dyn Fn(u32) -> u64) {
+        let values = &mut self.values;
+        for (i, gate) in self.gates.iter().enumerate() {
+            values[i] =
+                match *gate {
+                    Gate::And(a, b) => value(values, a) & value(values, b),
+                    Gate::Var(v) => pattern(v),
+                    Gate::False => 0
+                };
+        }
+    }
+
+    /// Like `simulate`, for the roots this was built for.
+    pub fn simulate(&mut self, patterns: &[u64]) -> Vec<u64> {
+        self.run(&|v| patterns[v as usize]);
+        self.roots.iter().map(|&r| value(&self.values, r)).collect()
+    }
+
+    /// The value of the first root when variable `v` is `assignment[v]`.
+    pub fn eval(&mut self, assignment: &[bool]) -> bool {
+        self.run(&|v| if assignment[v as usize] { !0 } else { 0 });
+        value(&self.values, self.roots[0]) & 1 == 1
+    }
+}
+
+fn value(values: &[u64], (i, complemented): (usize, bool)) -> u64 {
+    if complemented { !values[i] } else { values[i] }
+}
+
+/// Evaluates `roots` on 64 assignments at once. `patterns[v]` holds the 64
+/// values of variable `v`, one per bit, and bit `i` of each result is the
+/// value of that root under the assignment made of bit `i` of every pattern.
+pub fn simulate(aig: &Aig, roots: &[Lit], patterns: &[u64]) -> Vec<u64> {
+    Evaluator::new(aig, roots).simulate(patterns)
+}
+
+/// The value of `lit` when variable `v` is `assignment[v]`. To evaluate
+/// the same literal repeatedly, keep an `Evaluator` instead.
+pub fn eval(aig: &Aig, lit: Lit, assignment: &[bool]) -> bool {
+    Evaluator::new(aig, &[lit]).eval(assignment)
+}
+
+pub fn random_patterns(rng: &mut Rng, variables: usize) -> Vec<u64> {
+    (0..variables).map(|_| rng.next_u64()).collect()
+}
+
+// one more than the highest variable below the roots
+fn variables(aig: &Aig, roots: &[Lit]) -> usize {
+    aig.cone(roots).iter().filter_map(|&n| {
+        match aig.node(Lit::new(n, false)) {
+            Node::Var(v) => Some(v as usize + 1),
+            _ => None
+        }
+    }).max().unwrap_or(0)
+}
+
+/// Compares `a` and `b` on `rounds` batches of 64 random assignments. A
+/// `false` answer is certain, a `true` one only means no difference was found.
+pub fn probably_equivalent(aig: &Aig, a: Lit, b: Lit, rng: &mut Rng, rounds: usize) -> bool {
+    let n = variables(aig, &[a, b]);
+    let mut evaluator = Evaluator::new(aig, &[a, b]);
+    (0..rounds).all(|_| {
+        let values = evaluator.simulate(&random_patterns(rng, n));
+        values[0] == values[1]
+    })
+}
@@ -0,0 +1,565 @@
+use std::error;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+use std::time::Instant;
+
+use aig::Aig;
+use aig::Lit;
+use aig::Node;
+use aig::QBF;
+
+use compression::decompress;
+
+use count::Natural;
+use count::count_with_config;
+
+use dependency::relax_prefix;
+
+use dqbf;
+use dqbf::DQBF;
+
+use dqdimacs;
+
+use expand_solve::Control;
+use expand_solve::Observer;
+use expand_solve::SolverConfig;
+use expand_solve::eliminate_limited;
+use expand_solve::solve_traced;
+use expand_solve::solve_with_witness;
+
+use format;
+use format::Format;
+
+use preprocess::preprocess;
+use preprocess::quantifiers;
+
+use problem::Quantifier;
+use problem::Solution;
+use problem::UnknownReason;
+
+use reference;
+
+use simulate::Evaluator;
+
+use stats::SolveStats;
+
+use support::Supports;
+use support::cone_of_influence;
+
+use trace::TraceConfig;
+use trace::TraceFormat;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Engine {
+    // alternate expansion and simplification, the real solver
+    Expansion,
+    // try every assignment, for small problems and for checking
+    Reference
+}
+
+/// A problem ready to solve: a prefix and a matrix over its variables.
+#[derive(Debug, Clone)]
+pub struct Instance {
+    qbf: QBF
+}
+
+impl Instance {
+    pub fn variables(&self) -> u32 {
+        self.qbf.quantifier_blocks.iter().sum()
+    }
+
+    pub fn name(&self, var: u32) -> String {
+        self.qbf.name(var)
+    }
+
+    pub fn variable(&self, name: &str) -> Option<u32> {
+        self.qbf.variable(name)
+    }
+
+    /// The value of the matrix when variable `v` is `assignment[v]`.
+    pub fn eval(&self, assignment: &[bool]) -> bool {
+        Evaluator::new(&self.qbf.aig, &[self.qbf.expr]).eval(assignment)
+    }
+
+    /// The matrix on 64 assignments at once, one per bit of the patterns,
+    /// where `patterns[v]` holds the values of variable `v`.
+    pub fn simulate(&self, patterns: &[u64]) -> u64 {
+        Evaluator::new(&self.qbf.aig, &[self.qbf.expr]).simulate(patterns)[0]
+    }
+
+    /// The variables that the matrix depends on, in order.
+    pub fn support(&self) -> Vec<u32> {
+        Supports::new().support(&self.qbf.aig, self.qbf.expr).iter().collect()
+    }
+
+    /// The problem with `root`, a literal from the builder this came from, as
+    /// its matrix, and only the variables that `root` depends on.
+    pub fn cone(&self, root: Lit) -> Instance {
+        Instance { qbf: cone_of_influence(self.qbf.clone(), root) }
+    }
+
+    #[doc(hidden)]
+    pub fn qbf(&self) -> &QBF {
+        &self.qbf
+    }
+
+    #[doc(hidden)]
+    pub fn into_qbf(self) -> QBF {
+        self.qbf
+    }
+}
+
+#[doc(hidden)]
+impl From<QBF> for Instance {
+    fn from(qbf: QBF) -> Instance {
+        Instance { qbf }
+    }
+}
+
+/// Where and when to dump the graph during a solve: every expansion gets a
+/// line in `sizes.csv`, and the graph is written out after expanding one of
+/// the named variables and after every so many steps.
+#[derive(Debug, Clone)]
+pub struct Trace {
+    directory: PathBuf,
+    format: TraceFormat,
+    variables: Vec<String>,
+    every: Option<usize>
+}
+
+impl Trace {
+    pub fn new<P: Into<PathBuf>>(directory: P) -> Trace {
+        Trace { directory: directory.into(), format: TraceFormat::Native, variables: vec![], every: None }
+    }
+
+    pub fn format(mut self, format: TraceFormat) -> Trace {
+        self.format = format;
+        self
+    }
+
+    /// Dumps the graph after expanding the variable called `name`.
+    pub fn variable(mut self, name: &str) -> Trace {
+        self.variables.push(name.to_string());
+        self
+    }
+
+    pub fn every(mut self, steps: usize) -> Trace {
+        self.every = Some(steps);
+        self
+    }
+
+    // the names that `qbf` still has, as variables
+    fn resolve(&self, qbf: &QBF) -> TraceConfig {
+        TraceConfig {
+            directory: self.directory.clone(),
+            format: self.format,
+            vars: self.variables.iter().filter_map(|name| qbf.variable(name)).collect(),
+            every: self.every
+        }
+    }
+}
+
+/// How to solve. Built up from `Config::new()` one option at a time, e.g.
+/// `Config::new().timeout(Duration::from_secs(60)).witness(true)`.
+#[derive(Debug, Clone)]
+pub struct Config {
+    engine: Engine,
+    limits: SolverConfig,
+    format: Option<Format>,
+    preprocess: bool,
+    relax: bool,
+    trace: Option<Trace>,
+    witness: bool
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            engine: Engine::Expansion,
+            limits: SolverConfig::default(),
+            format: None,
+            preprocess: false,
+            relax: false,
+            trace: None,
+            witness: false
+        }
+    }
+}
+
+impl Config {
+    pub fn new() -> Config {
+        Config::default()
+    }
+
+    pub fn engine(mut self, engine: Engine) -> Config {
+        self.engine = engine;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Config {
+        self.limits.timeout = Some(timeout);
+        self
+    }
+
+    pub fn max_size(mut self, nodes: usize) -> Config {
+        self.limits.max_size = Some(nodes);
+        self
+    }
+
+    /// Expands up to `vars` variables of a block at once.
+    pub fn block_size(mut self, vars: usize) -> Config {
+        self.limits.block_size = vars;
+        self
+    }
+
+    /// Stops measuring the expression after every expansion, unless there
+    /// is a `max_size`.
+    pub fn skip_size(mut self, skip: bool) -> Config {
+        self.limits.skip_size = skip;
+        self
+    }
+
+    /// Never collects garbage while the graph has fewer than `nodes` nodes.
+    /// Collecting costs a pass over the graph, so small problems are better
+    /// off never doing it.
+    pub fn gc_min_nodes(mut self, nodes: usize) -> Config {
+        self.limits.gc_min_nodes = nodes;
+        self
+    }
+
+    /// Setting the flag makes a running solve give up as soon as it notices.
+    pub fn cancel(mut self, flag: Arc<AtomicBool>) -> Config {
+        self.limits.cancel = Some(flag);
+        self
+    }
+
+    /// The input format for `solve_file` and `solve_reader`. Without it, it
+    /// is guessed from the file name.
+    pub fn format(mut self, format: Format) -> Config {
+        self.format = Some(format);
+        self
+    }
+
+    /// Unit and pure literal elimination before solving. Can't be combined
+    /// with a witness, which would have to account for the variables that
+    /// were removed.
+    pub fn preprocess(mut self, preprocess: bool) -> Config {
+        self.preprocess = preprocess;
+        self
+    }
+
+    /// Moves variables out of their blocks where the dependency analysis
+    /// allows. Can't be combined with a witness, since the outermost block
+    /// can change.
+    pub fn relax_prefix(mut self, relax: bool) -> Config {
+        self.relax = relax;
+        self
+    }
+
+    /// Dumps the graph and its size history while expanding. A trace that
+    /// can't be written stops the solve with `Error::Io`.
+    pub fn trace(mut self, trace: Trace) -> Config {
+        self.trace = Some(trace);
+        self
+    }
+
+    #[doc(hidden)]
+    pub fn limits(&self) -> &SolverConfig {
+        &self.limits
+    }
+
+    /// Asks for an assignment to the outermost block that decides the
+    /// problem, see `expand_solve::solve_with_witness`.
+    pub fn witness(mut self, witness: bool) -> Config {
+        self.witness = witness;
+        self
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Parse(String),
+    // a variable in the matrix that the prefix does not bind
+    FreeVariable(u32),
+    // an assumption on a variable outside the outermost block, by name
+    Assumption(String),
+    // options that the chosen engine can't honour
+    Unsupported(&'static str),
+    // a limit was reached before there was an answer
+    Unknown(UnknownReason),
+    // a traced variable that the problem doesn't have
+    UnknownVariable(String)
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref e) => write!(f, "{}", e),
+            Error::Parse(ref e) => write!(f, "{}", e),
+            Error::FreeVariable(v) => write!(f, "variable {} is not quantified", v),
+            Error::Assumption(ref name) => write!(f, "variable {:?} is not in the outermost block", name),
+            Error::Unsupported(what) => write!(f, "{}", what),
+            Error::Unknown(reason) => write!(f, "gave up: {}", reason),
+            Error::UnknownVariable(ref name) => write!(f, "no variable {:?}", name)
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Value {
+    pub var: u32,
+    pub name: String,
+    pub value: bool
+}
+
+/// What the expansion engine did on the way to its answer.
+#[derive(Debug, Clone)]
+pub struct Statistics {
+    stats: SolveStats
+}
+
+impl Statistics {
+    pub fn expansions(&self) -> usize {
+        self.stats.steps.len()
+    }
+
+    /// The most nodes the graph held at once.
+    pub fn peak_nodes(&self) -> usize {
+        self.stats.memory.peak_nodes
+    }
+
+    pub fn collections(&self) -> usize {
+        self.stats.collections
+    }
+
+    pub fn time(&self) -> Duration {
+        self.stats.total_time
+    }
+
+    /// Everything, including each expansion and what preprocessing removed.
+    pub fn to_json(&self) -> String {
+        self.stats.to_json()
+    }
+
+    #[doc(hidden)]
+    pub fn details(&self) -> &SolveStats {
+        &self.stats
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SolveOutcome {
+    pub solution: Solution,
+    pub witness: Option<Vec<Value>>,
+    // only the expansion engine keeps statistics
+    pub stats: Option<Statistics>
+}
+
+/// The library's front door: reads, checks and solves problems according
+/// to a `Config`, and reports failures as `Error`s rather than panics.
+#[derive(Debug, Clone, Default)]
+pub struct Solver {
+    config: Config
+}
+
+/// Fails on a variable below `expr` that isn't one of `0..n_variables`.
+pub fn check_bound(aig: &Aig, expr: Lit, n_variables: u32) -> Result<(), Error> {
+    for n in aig.cone(&[expr]) {
+        if let Node::Var(v) = aig.node(Lit::new(n, false)) {
+            if v >= n_variables {
+                return Err(Error::FreeVariable(v));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_closed(qbf: &QBF) -> Result<(), Error> {
+    check_bound(&qbf.aig, qbf.expr, qbf.quantifier_blocks.iter().sum())
+}
+
+impl Solver {
+    pub fn new(config: Config) -> Solver {
+        Solver { config }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Reads a file in any supported format, compressed or not.
+    pub fn solve_file<P: AsRef<Path>>(&self, path: P) -> Result<SolveOutcome, Error> {
+        let path = path.as_ref();
+        let format = self.config.format.unwrap_or_else(|| Format::from_path(&path.to_string_lossy()));
+        let input = decompress(BufReader::new(File::open(path)?), Some(path))?;
+        self.read_and_solve(format, input)
+    }
+
+    /// Reads from `input`, which may be compressed. Without a format in the
+    /// config, the native format is assumed.
+    pub fn solve_reader<R: BufRead>(&self, input: R) -> Result<SolveOutcome, Error> {
+        let format = self.config.format.unwrap_or(Format::Native);
+        self.read_and_solve(format, decompress(input, None)?)
+    }
+
+    fn read_and_solve<R: BufRead>(&self, format: Format, input: R) -> Result<SolveOutcome, Error> {
+        if format == Format::Dqdimacs {
+            let dqbf = dqdimacs::read(input).map_err(Error::Parse)?;
+            return self.solve_dqbf(dqbf);
+        }
+        let qbf = format::read(format, input).map_err(Error::Parse)?;
+        self.solve(Instance { qbf })
+    }
+
+    /// Solves a problem with dependency sets as a QBF when they are nested,
+    /// and with the DQBF solver otherwise. That only takes the limits: it
+    /// keeps no statistics, and refuses the other options.
+    pub fn solve_dqbf(&self, dqbf: DQBF) -> Result<SolveOutcome, Error> {
+        check_bound(&dqbf.aig, dqbf.expr, dqbf.bindings.len() as u32)?;
+        let dqbf =
+            match dqbf.into_qbf() {
+                Ok(qbf) => return self.solve(Instance { qbf }),
+                Err(dqbf) => *dqbf
+            };
+        self.only_limits("the DQBF solver takes no engine, witness, trace, preprocessing or relaxation options")?;
+        let solution = dqbf::solve_with_config(dqbf, &self.config.limits);
+        Ok(SolveOutcome { solution, witness: None, stats: None })
+    }
+
+    // counting, elimination and the DQBF solver only have the expansion
+    // engine's limits
+    fn only_limits(&self, what: &'static str) -> Result<(), Error> {
+        let config = &self.config;
+        if config.engine != Engine::Expansion || config.witness || config.trace.is_some() || config.preprocess || config.relax {
+            return Err(Error::Unsupported(what));
+        }
+        Ok(())
+    }
+
+    /// How many assignments to the outermost block make the rest of the
+    /// problem true. When that block is universal the answer is 1 or 0.
+    pub fn count(&self, instance: Instance) -> Result<Natural, Error> {
+        self.only_limits("counting takes no engine, witness, trace, preprocessing or relaxation options")?;
+        check_closed(&instance.qbf)?;
+        count_with_config(instance.qbf, &self.config.limits).map_err(Error::Unknown)
+    }
+
+    /// Quantifies out every variable but the free inputs, and gives the
+    /// problem whose matrix is what is left: a function of the free inputs
+    /// that holds exactly where the rest of the problem is true.
+    pub fn eliminate(&self, instance: Instance) -> Result<Instance, Error> {
+        self.only_limits("elimination takes no engine, witness, trace, preprocessing or relaxation options")?;
+        let mut qbf = instance.qbf;
+        check_closed(&qbf)?;
+        let free_inputs = qbf.free_inputs;
+        let prefix: Vec<(Quantifier, u32)> = quantifiers(&qbf).into_iter().enumerate()
+            .skip(free_inputs as usize)
+            .map(|(v, q)| (q, v as u32))
+            .collect();
+        let expr = eliminate_limited(&mut qbf.aig, qbf.expr, &prefix, &self.config.limits, Instant::now())
+            .map_err(Error::Unknown)?;
+        qbf.expr = qbf.aig.collect(&[expr])[0];
+        qbf.first_quantifier = Quantifier::Exists;
+        qbf.last_quantifier = Quantifier::Exists;
+        qbf.quantifier_blocks = if free_inputs > 0 { vec![free_inputs] } else { vec![] };
+        qbf.names.truncate(free_inputs as usize);
+        Ok(Instance { qbf })
+    }
+
+    pub fn solve(&self, instance: Instance) -> Result<SolveOutcome, Error> {
+        self.solve_with(instance, &mut |_, _, _, _| Control::Continue)
+    }
+
+    /// `solve`, telling `observer` about every expansion. It can stop the
+    /// solve by returning `Control::Abort`.
+    pub fn solve_observed(&self, instance: Instance, observer: &mut dyn Observer) -> Result<SolveOutcome, Error> {
+        if self.config.engine == Engine::Reference {
+            return Err(Error::Unsupported("the reference engine has no expansions to observe"));
+        }
+        self.solve_with(instance, observer)
+    }
+
+    fn solve_with(&self, instance: Instance, observer: &mut dyn Observer) -> Result<SolveOutcome, Error> {
+        let qbf = instance.qbf;
+        check_closed(&qbf)?;
+        if let Some(ref trace) = self.config.trace {
+            if let Some(name) = trace.variables.iter().find(|name| qbf.variable(name).is_none()) {
+                return Err(Error::UnknownVariable(name.clone()));
+            }
+        }
+        if self.config.engine == Engine::Reference {
+            if self.config.witness {
+                return Err(Error::Unsupported("the reference engine gives no witnesses"));
+            }
+            if self.config.limits.max_size.is_some() {
+                return Err(Error::Unsupported("the reference engine builds no expression to limit the size of"));
+            }
+            if self.config.trace.is_some() {
+                return Err(Error::Unsupported("the reference engine has no expansions to trace"));
+            }
+        }
+        let witness = self.config.witness;
+        if witness && self.config.preprocess {
+            return Err(Error::Unsupported("preprocessing removes variables that a witness would need"));
+        }
+        if witness && self.config.relax {
+            return Err(Error::Unsupported("relaxing the prefix can change the block that a witness assigns"));
+        }
+        let (qbf, preprocessing) =
+            if self.config.preprocess {
+                let (qbf, stats) = preprocess(qbf);
+                (qbf, Some(stats))
+            } else {
+                (qbf, None)
+            };
+        let qbf =
+            if self.config.relax {
+                relax_prefix(qbf)
+            } else {
+                qbf
+            };
+
+        match self.config.engine {
+            Engine::Expansion => {
+                let names = qbf.names.clone();
+                let limits = &self.config.limits;
+                let trace = self.config.trace.as_ref().map(|trace| trace.resolve(&qbf));
+                let (solution, assignment, mut stats) =
+                    match trace {
+                        Some(ref trace) => solve_traced(qbf, limits, trace, observer, witness)?,
+                        None => solve_with_witness(qbf, limits, observer, witness)
+                    };
+                stats.preprocessing = preprocessing;
+                let witness = assignment.map(|assignment| {
+                    assignment.into_iter().map(|(var, value)| {
+                        let name = names.get(var as usize).cloned().unwrap_or_else(|| var.to_string());
+                        Value { var, name, value }
+                    }).collect()
+                });
+                Ok(SolveOutcome { solution, witness, stats: Some(Statistics { stats }) })
+            },
+            Engine::Reference => {
+                let start = Instant::now();
+                let solution = reference::solve_interruptible(&qbf, &|| self.config.limits.interrupted(start));
+                Ok(SolveOutcome { solution, witness: None, stats: None })
+            }
+        }
+    }
+}
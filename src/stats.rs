@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+use aig::Lit;
+use aig::MemoryStats;
+use aig::Node;
+use aig::QBF;
+use aig::SimplifyStats;
+use preprocess::PreprocessStats;
+use problem::Quantifier;
+use problem::opposite_quantifier;
+use support::Occurrences;
+use support::occurrences;
+
+#[derive(Debug, Clone)]
+pub struct ExpansionStep {
+    pub var: u32,
+    pub quantifier: Quantifier,
+    // `None` when the solver was told not to measure it
+    pub size: Option<usize>,
+    pub time: Duration
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SolveStats {
+    pub variables: u32,
+    // prefix variables that the matrix actually mentions, and its size
+    // once the input has been through the builder's simplifications
+    pub variables_in_support: usize,
+    pub initial_size: usize,
+    pub steps: Vec<ExpansionStep>,
+    pub collections: usize,
+    pub memory: MemoryStats,
+    pub simplifications: SimplifyStats,
+    // what preprocessing removed before the solve started, if it ran
+    pub preprocessing: Option<PreprocessStats>,
+    pub total_time: Duration
+}
+
+fn quantifier_name(q: Quantifier) -> &'static str {
+    match q {
+        Quantifier::Exists => "exists",
+        Quantifier::ForAll => "forall"
+    }
+}
+
+impl SolveStats {
+    pub fn to_json(&self) -> String {
+        let steps: Vec<String> = self.steps.iter().map(|step| {
+            format!(
+                "{{\"var\": {}, \"quantifier\": \"{}\", \"size\": {}, \"seconds\": {}}}",
+                step.var,
+                quantifier_name(step.quantifier),
+                step.size.map_or("null".to_string(), |s| s.to_string()),
+                step.time.as_secs_f64())
+        }).collect();
+        let s = &self.simplifications;
+
+        let mut out = String::from("{\n");
+        out.push_str(&format!("  \"variables\": {},\n", self.variables));
+        out.push_str(&format!("  \"variables_in_support\": {},\n", self.variables_in_support));
+        out.push_str(&format!("  \"initial_size\": {},\n", self.initial_size));
+        out.push_str(&format!("  \"seconds\": {},\n", self.total_time.as_secs_f64()));
+        out.push_str(&format!("  \"peak_nodes\": {},\n", self.memory.peak_nodes));
+        out.push_str(&format!("  \"final_nodes\": {},\n", self.memory.nodes));
+        out.push_str(&format!("  \"bytes\": {},\n", self.memory.bytes));
+        out.push_str(&format!("  \"collections\": {},\n", self.collections));
+        out.push_str(&format!(
+            "  \"simplifications\": {{\"hash_hits\": {}, \"trivial\": {}, \"absorption\": {}, \"two_level\": {}, \"contradiction\": {}, \"new_nodes\": {}}},\n",
+            s.hash_hits, s.trivial, s.absorption, s.two_level, s.contradiction, s.new_nodes));
+        out.push_str(&format!(
+            "  \"preprocessing\": {},\n",
+            self.preprocessing.as_ref().map_or("null".to_string(), |p| format!(
+                "{{\"variables_before\": {}, \"variables_after\": {}, \"size_before\": {}, \"size_after\": {}, \"units\": {}, \"pure_literals\": {}, \"unused\": {}}}",
+                p.variables_before, p.variables_after, p.size_before, p.size_after, p.units, p.pure_literals, p.unused))));
+        out.push_str("  \"steps\": [");
+        if !steps.is_empty() {
+            out.push_str("\n    ");
+            out.push_str(&steps.join(",\n    "));
+            out.push_str("\n  ");
+        }
+        out.push_str("]\n}");
+        out
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ProblemStats {
+    pub blocks: Vec<(Quantifier, u32)>,
+    pub and_nodes: usize,
+    pub var_nodes: usize,
+    pub depth: usize,
+    pub occurrences: Vec<Occurrences>
+}
+
+pub fn problem_stats(qbf: &QBF) -> ProblemStats {
+    let n_variables: u32 = qbf.quantifier_blocks.iter().sum();
+    let mut blocks = vec![];
+    let mut quantifier = qbf.first_quantifier;
+    for &block in &qbf.quantifier_blocks {
+        blocks.push((quantifier, block));
+        quantifier = opposite_quantifier(quantifier);
+    }
+
+    let mut stats = ProblemStats {
+        blocks,
+        and_nodes: 0,
+        var_nodes: 0,
+        depth: 0,
+        occurrences: occurrences(&qbf.aig, qbf.expr, n_variables)
+    };
+    let mut depths: HashMap<u32, usize> = HashMap::new();
+    for n in qbf.aig.cone(&[qbf.expr]) {
+        let depth =
+            match qbf.aig.node(Lit::new(n, false)) {
+                Node::And(a, b) => {
+                    stats.and_nodes += 1;
+                    1 + depths[&a.node()].max(depths[&b.node()])
+                },
+                Node::Var(_) => {
+                    stats.var_nodes += 1;
+                    0
+                },
+                Node::False => 0
+            };
+        depths.insert(n, depth);
+    }
+    stats.depth = depths[&qbf.expr.node()];
+
+    stats
+}
+
+impl fmt::Display for ProblemStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let prefix: Vec<String> = self.blocks.iter().map(|&(q, n)| {
+            format!("{}{}", if q == Quantifier::Exists { "e" } else { "a" }, n)
+        }).collect();
+        writeln!(f, "prefix: {}", prefix.join(" "))?;
+        writeln!(f, "variables: {}", self.occurrences.len())?;
+        writeln!(f, "variables in matrix: {}", self.var_nodes)?;
+        writeln!(f, "and nodes: {}", self.and_nodes)?;
+        writeln!(f, "depth: {}", self.depth)?;
+        write!(f, "occurrences:")?;
+        for (v, o) in self.occurrences.iter().enumerate() {
+            write!(f, " {}:+{}-{}", v, o.positive, o.negative)?;
+        }
+        writeln!(f)?;
+        // variables reached only through even or only through odd negations
+        write!(f, "pure:")?;
+        for (v, o) in self.occurrences.iter().enumerate() {
+            if o.even != o.odd {
+                write!(f, " {}{}", if o.even { "" } else { "-" }, v)?;
+            }
+        }
+        Ok(())
+    }
+}
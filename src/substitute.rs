@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+
+use aig::Aig;
+use aig::Lit;
+use aig::Node;
+
+use problem::UnknownReason;
+
+// how many nodes to rebuild between calls to `interrupted`
+const INTERRUPT_INTERVAL: usize = 4096;
+
+pub fn substitute(aig: &mut Aig, expr: Lit, variable: u32, value: bool) -> Lit {
+    substitute_interruptible(aig, expr, variable, value, &|| None).unwrap()
+}
+
+/// Like `substitute`, but gives up as soon as `interrupted` gives a reason,
+/// and returns that.
+pub fn substitute_interruptible(
+        aig: &mut Aig,
+        expr: Lit,
+        variable: u32,
+        value: bool,
+        interrupted: &dyn Fn() -> Option<UnknownReason>) -> Result<Lit, UnknownReason>
+{
+    let mut subs: HashMap<u32, Lit> = HashMap::new();
+
+    for (i, n) in aig.cone(&[expr]).into_iter().enumerate() {
+        if i % INTERRUPT_INTERVAL == INTERRUPT_INTERVAL - 1 {
+            if let Some(reason) = interrupted() {
+                return Err(reason);
+            }
+        }
+        let lit = Lit::new(n, false);
+        let outcome =
+            match aig.node(lit) {
+                Node::And(a, b) => {
+                    let a1 = subs[&a.node()].negate_if(a.is_complemented());
+                    let b1 = subs[&b.node()].negate_if(b.is_complemented());
+                    if a == a1 && b == b1 {
+                        lit
+                    } else {
+                        aig.and(a1, b1)
+                    }
+                },
+                Node::Var(v) if v == variable => {
+                    if value {
+                        Lit::TRUE
+                    } else {
+                        Lit::FALSE
+                    }
+                },
+                _ => lit
+            };
+        subs.insert(n, outcome);
+    }
+
+    Ok(subs[&expr.node()].negate_if(expr.is_complemented()))
+}
+
+/// Both cofactors of `expr` with respect to `variable`, false first, from
+/// one traversal that skips whatever can't depend on `variable`: the arena
+/// caches each node's top variable, and nothing below it is in the node's
+/// support. Expanding innermost first, the rest is exactly the part of the
+/// graph that depends on it.
+///
+/// The pairs are cached in the arena, so a node that turns up again in a
+/// later call, for the same variable, isn't split again. The incremental
+/// solver relies on that when a conjunct is added and the inner blocks are
+/// expanded anew.
+pub fn cofactor_pair(
+        aig: &mut Aig,
+        expr: Lit,
+        variable: u32,
+        interrupted: &dyn Fn() -> Option<UnknownReason>) -> Result<(Lit, Lit), UnknownReason>
+{
+    let depends = |aig: &Aig, l: Lit| aig.top_var(l).is_some_and(|t| t >= variable);
+    let done = |aig: &Aig, n: u32| aig.cached_cofactors(variable, n).is_some();
+    let pair = |aig: &Aig, l: Lit| {
+        if depends(aig, l) {
+            let (f, t) = aig.cached_cofactors(variable, l.node()).unwrap();
+            (f.negate_if(l.is_complemented()), t.negate_if(l.is_complemented()))
+        } else {
+            (l, l)
+        }
+    };
+
+    let mut to_visit = vec![];
+    if depends(aig, expr) {
+        to_visit.push((expr.node(), false));
+    }
+    let mut built = 0;
+    while let Some((n, children_done)) = to_visit.pop() {
+        if done(aig, n) {
+            continue;
+        }
+        let lit = Lit::new(n, false);
+        let outcome =
+            match aig.node(lit) {
+                Node::And(a, b) if !children_done => {
+                    to_visit.push((n, true));
+                    for &c in &[a, b] {
+                        if depends(aig, c) && !done(aig, c.node()) {
+                            to_visit.push((c.node(), false));
+                        }
+                    }
+                    continue;
+                },
+                Node::And(a, b) => {
+                    built += 1;
+                    if built % INTERRUPT_INTERVAL == 0 {
+                        if let Some(reason) = interrupted() {
+                            return Err(reason);
+                        }
+                    }
+                    let (af, at) = pair(aig, a);
+                    let (bf, bt) = pair(aig, b);
+                    (aig.and(af, bf), aig.and(at, bt))
+                },
+                Node::Var(v) if v == variable => (Lit::FALSE, Lit::TRUE),
+                _ => (lit, lit)
+            };
+        aig.cache_cofactors(variable, n, outcome);
+    }
+
+    Ok(pair(aig, expr))
+}
+
+// entries of length one are for nodes that don't depend on the variables
+fn pick(cofactors: &[Lit], combination: usize) -> Lit {
+    if cofactors.len() == 1 {
+        cofactors[0]
+    } else {
+        cofactors[combination]
+    }
+}
+
+/// Every cofactor of `expr` with respect to `vars`, from a single traversal.
+/// Bit `i` of a cofactor's index is the value given to `vars[i]`, so there
+/// are `2^vars.len()` of them.
+pub fn cofactors(
+        aig: &mut Aig,
+        expr: Lit,
+        vars: &[u32],
+        interrupted: &dyn Fn() -> Option<UnknownReason>) -> Result<Vec<Lit>, UnknownReason>
+{
+    let combinations = 1usize << vars.len();
+    let mut subs: HashMap<u32, Vec<Lit>> = HashMap::new();
+
+    for (i, n) in aig.cone(&[expr]).into_iter().enumerate() {
+        if i % INTERRUPT_INTERVAL == INTERRUPT_INTERVAL - 1 {
+            if let Some(reason) = interrupted() {
+                return Err(reason);
+            }
+        }
+        let lit = Lit::new(n, false);
+        let outcome =
+            match aig.node(lit) {
+                Node::And(a, b) => {
+                    let a_subs = &subs[&a.node()];
+                    let b_subs = &subs[&b.node()];
+                    if a_subs.len() == 1 && b_subs.len() == 1 {
+                        vec![lit]
+                    } else {
+                        (0..combinations).map(|c| {
+                            let a1 = pick(a_subs, c).negate_if(a.is_complemented());
+                            let b1 = pick(b_subs, c).negate_if(b.is_complemented());
+                            aig.and(a1, b1)
+                        }).collect()
+                    }
+                },
+                Node::Var(v) => {
+                    match vars.iter().position(|&x| x == v) {
+                        Some(i) => (0..combinations).map(|c| if c >> i & 1 == 1 { Lit::TRUE } else { Lit::FALSE }).collect(),
+                        None => vec![lit]
+                    }
+                },
+                Node::False => vec![lit]
+            };
+        subs.insert(n, outcome);
+    }
+
+    let root = &subs[&expr.node()];
+    Ok((0..combinations).map(|c| pick(root, c).negate_if(expr.is_complemented())).collect())
+}
+
+/// The same function as `expr` in `from`, built in `to`.
+pub fn copy(from: &Aig, to: &mut Aig, expr: Lit) -> Lit {
+    let mut copies: HashMap<u32, Lit> = HashMap::new();
+
+    for n in from.cone(&[expr]) {
+        let lit = Lit::new(n, false);
+        let outcome =
+            match from.node(lit) {
+                Node::And(a, b) => {
+                    let a1 = copies[&a.node()].negate_if(a.is_complemented());
+                    let b1 = copies[&b.node()].negate_if(b.is_complemented());
+                    to.and(a1, b1)
+                },
+                Node::Var(v) => to.var(v),
+                Node::False => lit
+            };
+        copies.insert(n, outcome);
+    }
+
+    copies[&expr.node()].negate_if(expr.is_complemented())
+}
+
+/// Replaces every variable `v` with `rename(v)`.
+pub fn rename(aig: &mut Aig, expr: Lit, rename: &dyn Fn(u32) -> u32) -> Lit {
+    let mut subs: HashMap<u32, Lit> = HashMap::new();
+
+    for n in aig.cone(&[expr]) {
+        let lit = Lit::new(n, false);
+        let outcome =
+            match aig.node(lit) {
+                Node::And(a, b) => {
+                    let a1 = subs[&a.node()].negate_if(a.is_complemented());
+                    let b1 = subs[&b.node()].negate_if(b.is_complemented());
+                    aig.and(a1, b1)
+                },
+                Node::Var(v) => aig.var(rename(v)),
+                Node::False => lit
+            };
+        subs.insert(n, outcome);
+    }
+
+    subs[&expr.node()].negate_if(expr.is_complemented())
+}
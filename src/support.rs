@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use aig::Aig;
+use aig::Lit;
+use aig::Node;
+use aig::QBF;
+
+use introduce::quantifier_blocks;
+
+use preprocess::quantifiers;
+
+use substitute::rename;
+
+/// A set of variables, one bit per variable index.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct VarSet(Vec<u64>);
+
+impl VarSet {
+    pub fn new() -> VarSet {
+        VarSet::default()
+    }
+
+    pub fn insert(&mut self, v: u32) {
+        let (word, bit) = ((v / 64) as usize, v % 64);
+        if word >= self.0.len() {
+            self.0.resize(word + 1, 0);
+        }
+        self.0[word] |= 1 << bit;
+    }
+
+    pub fn remove(&mut self, v: u32) {
+        if let Some(w) = self.0.get_mut((v / 64) as usize) {
+            *w &= !(1 << (v % 64));
+        }
+        // no trailing zero words, so that equal sets compare equal
+        while self.0.last() == Some(&0) {
+            self.0.pop();
+        }
+    }
+
+    pub fn contains(&self, v: u32) -> bool {
+        self.0.get((v / 64) as usize).is_some_and(|w| w >> (v % 64) & 1 == 1)
+    }
+
+    pub fn union_with(&mut self, other: &VarSet) {
+        if other.0.len() > self.0.len() {
+            self.0.resize(other.0.len(), 0);
+        }
+        for (w, o) in self.0.iter_mut().zip(&other.0) {
+            *w |= o;
+        }
+    }
+
+    pub fn is_subset(&self, other: &VarSet) -> bool {
+        self.0.iter().enumerate().all(|(i, &w)| w & !other.0.get(i).cloned().unwrap_or(0) == 0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|&w| w == 0)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=u32> + '_ {
+        self.0.iter().enumerate().flat_map(|(i, &w)| {
+            (0..64).filter(move |b| w >> b & 1 == 1).map(move |b| i as u32 * 64 + b)
+        })
+    }
+}
+
+/// The support of every node asked about, and of everything below it,
+/// kept for later questions. Nodes never change once built, so the cache
+/// stays valid as the graph grows, but not across `Aig::collect`.
+#[derive(Debug, Default)]
+pub struct Supports {
+    sets: HashMap<u32, VarSet>
+}
+
+impl Supports {
+    pub fn new() -> Supports {
+        Supports::default()
+    }
+
+    /// The variables `lit` depends on.
+    pub fn support(&mut self, aig: &Aig, lit: Lit) -> &VarSet {
+        let mut to_visit = vec![(lit.node(), false)];
+        while let Some((n, children_done)) = to_visit.pop() {
+            if self.sets.contains_key(&n) {
+                continue;
+            }
+            let set =
+                match aig.node(Lit::new(n, false)) {
+                    Node::And(a, b) if !children_done => {
+                        to_visit.push((n, true));
+                        to_visit.push((a.node(), false));
+                        to_visit.push((b.node(), false));
+                        continue;
+                    },
+                    Node::And(a, b) => {
+                        let mut set = self.sets[&a.node()].clone();
+                        set.union_with(&self.sets[&b.node()]);
+                        set
+                    },
+                    Node::Var(v) => {
+                        let mut set = VarSet::new();
+                        set.insert(v);
+                        set
+                    },
+                    Node::False => VarSet::new()
+                };
+            self.sets.insert(n, set);
+        }
+        &self.sets[&lit.node()]
+    }
+
+    pub fn depends_on(&mut self, aig: &Aig, lit: Lit, v: u32) -> bool {
+        self.support(aig, lit).contains(v)
+    }
+}
+
+/// How a variable occurs below a root.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Occurrences {
+    // and gates that take it as an input, plain and complemented, counting
+    // the root as one more
+    pub positive: usize,
+    pub negative: usize,
+    // whether some path from the root reaches it through an even, and an
+    // odd, number of negations; a variable reached only one way is pure
+    pub even: bool,
+    pub odd: bool
+}
+
+/// Occurrences of every variable below `root`, indexed by variable.
+pub fn occurrences(aig: &Aig, root: Lit, n_variables: u32) -> Vec<Occurrences> {
+    let mut occurrences = vec![Occurrences::default(); n_variables as usize];
+    let mut edges = vec![root];
+    for n in aig.cone(&[root]) {
+        if let Node::And(a, b) = aig.node(Lit::new(n, false)) {
+            edges.push(a);
+            edges.push(b);
+        }
+    }
+    for x in edges {
+        if let Node::Var(v) = aig.node(x) {
+            if let Some(o) = occurrences.get_mut(v as usize) {
+                if x.is_complemented() {
+                    o.negative += 1;
+                } else {
+                    o.positive += 1;
+                }
+            }
+        }
+    }
+    for (v, (even, odd)) in polarities(aig, root) {
+        if let Some(o) = occurrences.get_mut(v as usize) {
+            o.even = even;
+            o.odd = odd;
+        }
+    }
+    occurrences
+}
+
+/// For every variable below `root`, whether it is reached through an even
+/// and through an odd number of negations.
+pub fn polarities(aig: &Aig, root: Lit) -> HashMap<u32, (bool, bool)> {
+    let mut visited = HashSet::new();
+    let mut polarities: HashMap<u32, (bool, bool)> = HashMap::new();
+    let mut to_visit = vec![root];
+
+    while let Some(l) = to_visit.pop() {
+        if !visited.insert(l) {
+            continue;
+        }
+        match aig.node(l) {
+            Node::And(a, b) => {
+                to_visit.push(a.negate_if(l.is_complemented()));
+                to_visit.push(b.negate_if(l.is_complemented()));
+            },
+            Node::Var(v) => {
+                let p = polarities.entry(v).or_insert((false, false));
+                if l.is_complemented() {
+                    p.1 = true;
+                } else {
+                    p.0 = true;
+                }
+            },
+            Node::False => {}
+        }
+    }
+
+    polarities
+}
+
+/// The part of `qbf` that `root` depends on: `root` as the matrix, and only
+/// the variables in its support, renumbered in prefix order but keeping
+/// their names.
+pub fn cone_of_influence(mut qbf: QBF, root: Lit) -> QBF {
+    let support = Supports::new().support(&qbf.aig, root).clone();
+    let quantifiers = quantifiers(&qbf);
+
+    let mut numbering = HashMap::new();
+    let mut remaining = vec![];
+    let mut names = vec![];
+    let mut free_inputs = 0;
+    for (v, &q) in quantifiers.iter().enumerate() {
+        if support.contains(v as u32) {
+            if (v as u32) < qbf.free_inputs {
+                free_inputs += 1;
+            }
+            numbering.insert(v as u32, remaining.len() as u32);
+            remaining.push(q);
+            names.push(qbf.name(v as u32));
+        }
+    }
+
+    let expr = rename(&mut qbf.aig, root, &|v| numbering[&v]);
+    qbf.expr = qbf.aig.collect(&[expr])[0];
+    let (first_quantifier, last_quantifier, blocks) = quantifier_blocks(&remaining);
+    qbf.first_quantifier = first_quantifier;
+    qbf.last_quantifier = last_quantifier;
+    qbf.quantifier_blocks = blocks;
+    qbf.names = names;
+    qbf.free_inputs = free_inputs;
+    qbf
+}
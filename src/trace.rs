@@ -0,0 +1,71 @@
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::path::PathBuf;
+
+use aig::QBF;
+
+use dot;
+use printout::printout_qbf;
+
+use problem::Quantifier;
+
+use stats::ExpansionStep;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TraceFormat {
+    Native,
+    Dot
+}
+
+/// Where and when to dump the graph during a solve. Every expansion gets a
+/// line in `sizes.csv`; the graph itself is written as `stepNNNNNN.qbf` or
+/// `.dot` after expanding one of `vars`, and after every `every` steps.
+#[derive(Debug, Clone)]
+pub struct TraceConfig {
+    pub directory: PathBuf,
+    pub format: TraceFormat,
+    pub vars: Vec<u32>,
+    pub every: Option<usize>
+}
+
+pub struct Tracer<'a> {
+    config: &'a TraceConfig,
+    csv: File
+}
+
+impl<'a> Tracer<'a> {
+    pub fn new(config: &'a TraceConfig) -> io::Result<Tracer<'a>> {
+        fs::create_dir_all(&config.directory)?;
+        let mut csv = File::create(config.directory.join("sizes.csv"))?;
+        writeln!(csv, "step,var,name,quantifier,size,nodes,seconds")?;
+        Ok(Tracer { config, csv })
+    }
+
+    /// `vars` are those that the step expanded together.
+    pub fn wants_dump(&self, step: usize, vars: &[u32]) -> bool {
+        vars.iter().any(|v| self.config.vars.contains(v)) || self.config.every.is_some_and(|k| step.is_multiple_of(k))
+    }
+
+    // one line per step, written straight away so that it survives a solve
+    // that never finishes
+    pub fn record(&mut self, number: usize, step: &ExpansionStep, name: &str, nodes: usize) -> io::Result<()> {
+        let q = if step.quantifier == Quantifier::Exists { "e" } else { "a" };
+        let size = step.size.map_or(String::new(), |s| s.to_string());
+        writeln!(self.csv, "{},{},\"{}\",{},{},{},{:.6}",
+            number, step.var, name.replace('"', "\"\""), q, size, nodes, step.time.as_secs_f64())?;
+        self.csv.flush()
+    }
+
+    /// `qbf` is what is left after `step` expansions: the remaining prefix
+    /// and the current matrix.
+    pub fn dump(&self, step: usize, qbf: &QBF) -> io::Result<()> {
+        let (text, extension) =
+            match self.config.format {
+                TraceFormat::Native => (printout_qbf(qbf), "qbf"),
+                TraceFormat::Dot => (dot::printout_qbf(qbf), "dot")
+            };
+        fs::write(self.config.directory.join(format!("step{:06}.{}", step, extension)), text)
+    }
+}
@@ -0,0 +1,128 @@
+use std::io::Write;
+use std::process::Command;
+use std::process::Stdio;
+
+// runs the binary on `input` as stdin, giving its exit code and stdout
+fn run(args: &[&str], input: &str) -> (i32, String, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_qbf"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // usage errors exit without reading, which breaks the pipe
+    let _ = child.stdin.take().unwrap().write_all(input.as_bytes());
+    let output = child.wait_with_output().unwrap();
+    (
+        output.status.code().unwrap(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap()
+    )
+}
+
+const SAT: &str = "p cnf 9 2\ne 7 3 0\na 2 0\n7 2 0\n3 -2 0\n";
+const UNSAT: &str = "p cnf 2 2\na 1 0\ne 2 0\n1 2 0\n1 -2 0\n";
+
+#[test]
+fn exit_codes() {
+    assert_eq!(run(&["--from", "qdimacs"], SAT), (10, "s cnf 1\n".to_string(), String::new()));
+    assert_eq!(run(&["solve", "--from", "qdimacs", "-"], UNSAT), (20, "s cnf 0\n".to_string(), String::new()));
+    let (code, out, _) = run(&["--from", "qdimacs", "--timeout", "0"], SAT);
+    assert_eq!((code, &out[..]), (0, "s cnf -1\n"));
+
+    let (code, out, err) = run(&["/nonexistent/problem.qdimacs"], "");
+    assert_eq!((code, &out[..]), (1, ""));
+    assert!(err.contains("/nonexistent/problem.qdimacs"), "{}", err);
+    let (code, _, err) = run(&["--frobnicate"], "");
+    assert_eq!(code, 1);
+    assert!(err.starts_with("usage:"), "{}", err);
+    let (code, _, err) = run(&["--trace", "/nonexistent", "--trace-every", "0"], "");
+    assert_eq!(code, 1);
+    assert!(err.starts_with("--trace-every expects a positive number\nusage:"), "{}", err);
+}
+
+#[test]
+fn witness_lines() {
+    // QDIMACS numbers are kept
+    let (code, out, _) = run(&["--from", "qdimacs", "--witness"], SAT);
+    assert_eq!(code, 10);
+    assert_eq!(out, "s cnf 1\nV 7 0\nV 3 0\n");
+
+    // one name that looks like a number doesn't make the others numbers:
+    // all of them go by position
+    let native = "exists a\nexists 1\nforall b\nx = or(a, b)\ny = and(x, ~1)\nz = or(y, b)\nw = and(y, z)\nw\n";
+    let (code, out, _) = run(&["--witness"], native);
+    assert_eq!(code, 10);
+    assert_eq!(out, "s cnf 1\nV 1 0\nV -2 0\n");
+
+    let (code, out, _) = run(&["--from", "qdimacs", "--witness"], UNSAT);
+    assert_eq!(code, 20);
+    assert_eq!(out, "s cnf 0\nV -1 0\n");
+}
+
+#[test]
+fn dependency_sets() {
+    let crossed = "p cnf 4 2\na 1 2 0\nd 3 1 0\nd 4 2 0\n3 -1 0\n-3 1 0\n4 -2 0\n-4 2 0\n";
+    assert_eq!(run(&["--from", "dqdimacs"], crossed).0, 10);
+    let (code, _, err) = run(&["--from", "dqdimacs", "--stats", "json"], crossed);
+    assert_eq!(code, 1);
+    assert!(err.contains("keeps no statistics"), "{}", err);
+    let (code, _, err) = run(&["--from", "dqdimacs", "--witness"], crossed);
+    assert_eq!(code, 1);
+    assert!(err.contains("the DQBF solver takes no"), "{}", err);
+
+    // nested dependency sets are an ordinary QBF, with everything that has
+    let nested = "p cnf 3 2\na 1 2 0\nd 3 1 0\n3 -2 0\n-3 2 0\n";
+    let (code, _, err) = run(&["--from", "dqdimacs", "--stats", "json"], nested);
+    assert_eq!(code, 20);
+    assert!(err.starts_with('{'), "{}", err);
+}
+
+#[test]
+fn convert_round_trips() {
+    for to in &["native", "qcir", "qdimacs"] {
+        let (code, converted, _) = run(&["convert", "--from", "qdimacs", "--to", to], SAT);
+        assert_eq!(code, 0);
+        assert_eq!(run(&["--from", to], &converted).0, 10, "{}", to);
+        let (_, back, _) = run(&["convert", "--from", to, "--to", "qdimacs"], &converted);
+        assert_eq!(run(&["--from", "qdimacs"], &back).0, 10, "{}", to);
+    }
+    assert_eq!(run(&["convert", "--from", "qdimacs"], SAT).0, 1);
+}
+
+#[test]
+fn inspecting_a_problem() {
+    let (code, out, _) = run(&["stats", "--from", "qdimacs"], SAT);
+    assert_eq!(code, 0);
+    assert!(out.starts_with("prefix: e2 a1\nvariables: 3\n"), "{}", out);
+
+    let (code, out, _) = run(&["dot", "--from", "qdimacs"], SAT);
+    assert_eq!(code, 0);
+    assert!(out.starts_with("digraph aig {\n") && out.ends_with("}\n"), "{}", out);
+    assert!(out.contains("label=\"∀ 2\""), "{}", out);
+
+    // the outer block is 7 and 3, and only both true works
+    assert_eq!(run(&["count", "--from", "qdimacs"], SAT), (0, "1\n".to_string(), String::new()));
+    assert_eq!(run(&["count", "--from", "qdimacs"], UNSAT).1, "0\n");
+}
+
+#[test]
+fn simplifying_and_cutting() {
+    // preprocessing decides this one outright
+    let (code, out, _) = run(&["simplify", "--from", "qdimacs", "--to", "native"], SAT);
+    assert_eq!(code, 0);
+    assert_eq!(run(&[], &out).0, 10);
+    assert_eq!(run(&["simplify", "--from", "qdimacs"], UNSAT).1, "p cnf 0 1\n0\n");
+
+    let native = "exists a\nforall b\nexists c\nx = and(a, c)\ny = or(x, b)\ny\n";
+    let (code, out, _) = run(&["cone", "--root", "4"], native);
+    assert_eq!(code, 0);
+    assert_eq!(out, "exists a\nexists c\nv1 = and(a, c)\nv1\n");
+    // the graph names nodes the way --root takes them
+    let (_, out, _) = run(&["dot", "--root", "4"], native);
+    assert!(out.contains("  n4 [label=\"and\"];\n  n4 -> n1 [label=\"l\"];\n"), "{}", out);
+    assert!(out.contains("  n1 [label=\"∃ a\"];\n"), "{}", out);
+    assert_eq!(run(&["cone", "--root", "99"], native).0, 1);
+    assert_eq!(run(&["cone"], native).0, 1);
+}